@@ -1,5 +1,5 @@
 use anyhow::Error;
-use lastpass::{endpoints, DecryptionKey, LoginKey};
+use lastpass::{endpoints, endpoints::RetryPolicy};
 use reqwest::Client;
 use structopt::StructOpt;
 
@@ -17,13 +17,21 @@ async fn main() -> Result<(), Error> {
         .cookie_store(true)
         .build()?;
 
+    // Transient failures (e.g. a 503 or connection reset) will be retried
+    // with exponential backoff
+    let retry = RetryPolicy::default();
+
     // How many times should we iterate when generating keys?
     let iterations =
-        endpoints::iterations(&client, &args.host, &args.username).await?;
+        endpoints::iterations(&client, &args.host, &args.username, &retry)
+            .await?;
 
-    // create a key which can be used to log in
-    let login_key =
-        LoginKey::calculate(&args.username, &args.password, iterations);
+    // create a key which can be used to log in, and the master decryption key
+    // used to decrypt the vault (note: these are separate keys, but deriving
+    // both at once lets us skip a very expensive calculation)
+    let (login_key, decryption_key) =
+        lastpass::derive_keys_async(&args.username, &args.password, iterations)
+            .await;
 
     // send a login request and initialise our user session
     let session = endpoints::login(
@@ -43,20 +51,16 @@ async fn main() -> Result<(), Error> {
     // (a potentially expensive request) by using this number to see whether a
     // cached version is still valid.
     let vault_version =
-        endpoints::get_vault_version(&client, &args.host).await?;
+        endpoints::get_vault_version(&client, &args.host, &retry).await?;
     log::info!("Current vault version: {}", vault_version);
 
-    // We need our master decryption key to decrypt the vault (note: this is
-    // separate to the login key)
-    let decryption_key =
-        DecryptionKey::calculate(&args.username, &args.password, iterations);
-
     // grab a snapshot of the vault
     let vault = endpoints::get_vault(
         &client,
         &args.host,
         &decryption_key,
         &session.private_key,
+        &retry,
     )
     .await?;
 
@@ -101,6 +105,7 @@ async fn main() -> Result<(), Error> {
                 &session.token,
                 &attachment.storage_key,
                 &attachment_key,
+                &retry,
             )
             .await?;
 
@@ -139,10 +144,5 @@ struct Args {
 }
 
 impl Args {
-    pub fn trusted_id(&self) -> Option<&str> {
-        match self.trusted_id {
-            Some(ref id) => Some(&id),
-            None => None,
-        }
-    }
+    pub fn trusted_id(&self) -> Option<&str> { self.trusted_id.as_deref() }
 }
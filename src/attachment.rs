@@ -23,12 +23,50 @@ pub struct Attachment {
 }
 
 impl Attachment {
+    /// Decrypt the attachment's filename.
     pub fn filename(
         &self,
         attachment_key: &DecryptionKey,
     ) -> Result<String, DecryptionError> {
-        attachment_key
-            .decrypt_base64(&self.encrypted_filename)
-            .map(|filename| String::from_utf8(filename).unwrap())
+        let filename =
+            attachment_key.decrypt_base64(&self.encrypted_filename)?;
+
+        Ok(String::from_utf8(filename)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(encrypted_filename: String) -> Attachment {
+        Attachment {
+            id: Id::from("1234-1"),
+            parent: Id::from("1234"),
+            mime_type: String::from("other:txt"),
+            storage_key: String::from("100000027282"),
+            size: 42,
+            encrypted_filename,
+        }
+    }
+
+    #[test]
+    fn decrypt_the_filename() {
+        let key = DecryptionKey::generate();
+        let attachment = attachment(key.encrypt_base64("notes.txt"));
+
+        let got = attachment.filename(&key).unwrap();
+
+        assert_eq!(got, "notes.txt");
+    }
+
+    #[test]
+    fn filenames_must_be_valid_utf8() {
+        let key = DecryptionKey::generate();
+        let attachment = attachment(key.encrypt_base64([0xff, 0xfe, 0xfd]));
+
+        let got = attachment.filename(&key).unwrap_err();
+
+        assert!(matches!(got, DecryptionError::InvalidUtf8(_)));
     }
 }
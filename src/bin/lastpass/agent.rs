@@ -0,0 +1,405 @@
+//! A background process which remembers the decryption key, so the master
+//! password only needs to be typed in every now and then.
+//!
+//! After the vault has been unlocked we start `lastpass agent` and hand it the
+//! key over a pipe. It listens on a Unix socket in the [`Store`]'s directory
+//! and will only talk to processes belonging to the same user (checked using
+//! `SO_PEERCRED`). The agent exits (forgetting the key) when the user logs
+//! out or after `$LASTPASS_AGENT_TIMEOUT` seconds, which defaults to an hour
+//! and can be set to `0` to never time out. The timeout is counted from when
+//! the agent started, so using it doesn't keep it alive for longer. Set
+//! `$LASTPASS_AGENT_DISABLE=1` to turn it off altogether.
+
+use crate::store::{SavedSession, Store};
+use anyhow::{Context, Error};
+use lastpass::DecryptionKey;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+        process::CommandExt,
+    },
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// How long either side will wait for the other before giving up.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Start an agent in the background which remembers the `decryption_key`,
+/// replacing any agent which is already running.
+pub fn start(
+    store: &Store,
+    session: &SavedSession,
+    decryption_key: &DecryptionKey,
+) -> Result<(), Error> {
+    if disabled() {
+        return Ok(());
+    }
+
+    lock(store)?;
+
+    let exe = std::env::current_exe()
+        .context("Unable to find the lastpass executable")?;
+    let mut child = Command::new(exe)
+        .arg("agent")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // so hitting Ctrl-C in the terminal doesn't kill the agent too
+        .process_group(0)
+        .spawn()
+        .context("Unable to start the agent")?;
+
+    // the key is passed over a pipe so it won't show up in the process list
+    let handover = Handover {
+        session: session.clone(),
+        decryption_key: hex::encode(&**decryption_key),
+    };
+    let stdin = child.stdin.take().context("The agent has no stdin")?;
+    serde_json::to_writer(stdin, &handover)
+        .context("Unable to send the key to the agent")?;
+
+    Ok(())
+}
+
+/// Ask the agent for the decryption key, if it is running and was started
+/// for the same `session`.
+pub fn decryption_key(
+    store: &Store,
+    session: &SavedSession,
+) -> Option<DecryptionKey> {
+    if disabled() {
+        return None;
+    }
+
+    match request(&store.agent_socket(), &Request::Unlock) {
+        Ok(Response::Unlocked {
+            session: ref agent_session,
+            ref decryption_key,
+        }) if agent_session == session => {
+            DecryptionKey::from_hex(decryption_key).ok()
+        },
+        Ok(Response::Unlocked { .. }) => {
+            log::debug!("The agent was started for a different session");
+            None
+        },
+        Ok(other) => {
+            log::debug!("Unexpected response from the agent: {:?}", other);
+            None
+        },
+        Err(e) if is_not_running(&e) => None,
+        Err(e) => {
+            log::warn!("Unable to talk to the agent: {}", e);
+            None
+        },
+    }
+}
+
+/// Tell the agent (if there is one) to forget the key and exit.
+pub fn lock(store: &Store) -> Result<(), Error> {
+    let socket = store.agent_socket();
+
+    match request(&socket, &Request::Lock) {
+        Ok(_) => Ok(()),
+        Err(e) if is_not_running(&e) => {
+            // clean up after an agent which didn't exit cleanly
+            remove_socket(&socket)?;
+            Ok(())
+        },
+        Err(e) => Err(Error::from(e).context("Unable to stop the agent")),
+    }
+}
+
+/// The entry point for the agent process (`lastpass agent`).
+pub fn run(store: &Store) -> Result<(), Error> {
+    let mut raw = String::new();
+    io::stdin()
+        .read_to_string(&mut raw)
+        .context("Unable to read the key")?;
+    let handover: Handover =
+        serde_json::from_str(&raw).context("Unable to parse the handover")?;
+    let decryption_key = DecryptionKey::from_hex(&handover.decryption_key)
+        .context("Invalid decryption key")?;
+
+    let socket = store.agent_socket();
+    remove_socket(&socket)?;
+    let listener = UnixListener::bind(&socket).with_context(|| {
+        format!("Unable to listen on \"{}\"", socket.display())
+    })?;
+    fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))?;
+
+    if let Some(timeout) = timeout() {
+        let socket = socket.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(timeout);
+            let _ = fs::remove_file(&socket);
+            std::process::exit(0);
+        });
+    }
+
+    Server::new(handover.session, decryption_key).serve(&listener);
+    remove_socket(&socket)?;
+
+    Ok(())
+}
+
+fn disabled() -> bool {
+    matches!(std::env::var("LASTPASS_AGENT_DISABLE"), Ok(value) if value == "1")
+}
+
+/// How long the agent should stay alive after starting (regardless of how
+/// often it is used), or `None` to never time out.
+fn timeout() -> Option<Duration> {
+    let raw = match std::env::var("LASTPASS_AGENT_TIMEOUT") {
+        Ok(raw) => raw,
+        Err(_) => return Some(DEFAULT_TIMEOUT),
+    };
+
+    match raw.trim().parse::<u64>() {
+        Ok(0) => None,
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            log::warn!("Ignoring invalid $LASTPASS_AGENT_TIMEOUT: {:?}", raw);
+            Some(DEFAULT_TIMEOUT)
+        },
+    }
+}
+
+fn is_not_running(e: &io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused)
+}
+
+fn remove_socket(socket: &Path) -> Result<(), Error> {
+    match fs::remove_file(socket) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(Error::from(e)
+            .context(format!("Unable to remove \"{}\"", socket.display()))),
+    }
+}
+
+/// Send a single request to the agent and wait for its response.
+fn request(socket: &Path, request: &Request) -> io::Result<Response> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    send(&mut stream, request)?;
+    receive(&mut BufReader::new(stream))
+}
+
+fn send<T: serde::Serialize>(
+    stream: &mut UnixStream,
+    msg: &T,
+) -> io::Result<()> {
+    let mut line = serde_json::to_vec(msg)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+fn receive<T, R>(reader: &mut R) -> io::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+    R: BufRead,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "The connection was closed",
+        ));
+    }
+
+    serde_json::from_str(&line).map_err(io::Error::from)
+}
+
+/// What the agent is told when it starts up.
+#[derive(Debug, Serialize, Deserialize)]
+struct Handover {
+    session: SavedSession,
+    decryption_key: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+enum Request {
+    /// Get the session and decryption key.
+    Unlock,
+    /// Forget the key and exit.
+    Lock,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+enum Response {
+    Unlocked {
+        session: SavedSession,
+        /// The hex-encoded decryption key.
+        decryption_key: String,
+    },
+    Locked,
+}
+
+/// The agent's side of the socket.
+struct Server {
+    session: SavedSession,
+    decryption_key: DecryptionKey,
+}
+
+impl Server {
+    fn new(session: SavedSession, decryption_key: DecryptionKey) -> Self {
+        Server {
+            session,
+            decryption_key,
+        }
+    }
+
+    /// Answer requests until we're asked to lock.
+    fn serve(&self, listener: &UnixListener) {
+        for stream in listener.incoming() {
+            let result = stream
+                .map_err(Error::from)
+                .and_then(|stream| self.handle(stream));
+
+            match result {
+                Ok(Some(Request::Lock)) => return,
+                Ok(_) => {},
+                Err(e) => log::warn!("Unable to handle a request: {}", e),
+            }
+        }
+    }
+
+    fn handle(&self, mut stream: UnixStream) -> Result<Option<Request>, Error> {
+        let peer = peer_uid(&stream).context("Unable to check the peer")?;
+        if peer != current_uid() {
+            log::warn!("Rejecting a connection from user {}", peer);
+            return Ok(None);
+        }
+
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        let request: Request = receive(&mut BufReader::new(&stream))?;
+        let response = match request {
+            Request::Unlock => Response::Unlocked {
+                session: self.session.clone(),
+                decryption_key: hex::encode(&*self.decryption_key),
+            },
+            Request::Lock => Response::Locked,
+        };
+        send(&mut stream, &response)?;
+
+        Ok(Some(request))
+    }
+}
+
+/// The user ID of the process on the other end of a socket.
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    // SAFETY: the file descriptor is valid for as long as we borrow the
+    // stream, and `credentials` is a `ucred` large enough for SO_PEERCRED
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret == 0 {
+        Ok(credentials.uid)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Without `SO_PEERCRED` we can't tell who is connecting, so nobody gets in.
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(_stream: &UnixStream) -> io::Result<u32> {
+    Err(io::Error::new(
+        ErrorKind::Other,
+        "Checking the peer's credentials is only supported on Linux",
+    ))
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid() has no preconditions and always succeeds
+    unsafe { libc::getuid() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> SavedSession {
+        SavedSession {
+            username: String::from("joe.bloggs@example.com"),
+            host: String::from("lastpass.com"),
+            uid: String::from("1234"),
+            token: String::from("token"),
+            session_id: String::from("session"),
+            iterations: 100100,
+            private_key: String::new(),
+        }
+    }
+
+    #[test]
+    fn peers_are_the_current_user() {
+        let (a, _b) = UnixStream::pair().unwrap();
+
+        assert_eq!(peer_uid(&a).unwrap(), current_uid());
+    }
+
+    #[test]
+    fn unlock_then_lock() {
+        let dir = std::env::temp_dir()
+            .join(format!("lastpass-agent-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("agent.sock");
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let key = DecryptionKey::generate();
+        let server = Server::new(session(), key);
+        let handle = std::thread::spawn(move || server.serve(&listener));
+
+        let got = request(&socket, &Request::Unlock).unwrap();
+
+        assert_eq!(
+            got,
+            Response::Unlocked {
+                session: session(),
+                decryption_key: hex::encode(&*key),
+            }
+        );
+
+        assert_eq!(request(&socket, &Request::Lock).unwrap(), Response::Locked);
+        handle.join().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_missing_agent_is_not_running() {
+        let socket = std::env::temp_dir().join("lastpass-agent-missing.sock");
+
+        let err = request(&socket, &Request::Unlock).unwrap_err();
+
+        assert!(is_not_running(&err));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{commands, store::Store, template};
+use anyhow::Error;
+use lastpass::Account;
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub struct Add {
+    /// The new entry's name, optionally including its folder (e.g.
+    /// "Work/Email").
+    name: String,
+    /// Create a secure note of this type (e.g. "Generic" or "Server")
+    /// instead of a website login.
+    #[structopt(long, value_name = "TYPE")]
+    note_type: Option<String>,
+    /// The website's URL, which can also be changed in the editor.
+    #[structopt(long, default_value = "https://example.com/")]
+    url: Url,
+    /// Read the filled-in template from stdin instead of opening an editor.
+    #[structopt(long)]
+    non_interactive: bool,
+}
+
+impl Add {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let (vault, decryption_key) =
+            commands::unlock(&store, &session).await?;
+
+        let mut account = self.new_account();
+        let edited = commands::edit_text(
+            &template::render(&account),
+            self.non_interactive,
+        )?;
+        template::apply(&edited, &mut account)?;
+        account.share = vault
+            .share_for_folder(&account.folder())
+            .map(|share| share.id.clone());
+
+        commands::save_account(
+            &store,
+            &session,
+            &vault,
+            &mut account,
+            &decryption_key,
+        )
+        .await?;
+        println!("Added {} [id: {}]", account.path(), &*account.id);
+
+        Ok(())
+    }
+
+    fn new_account(&self) -> Account {
+        let (folder, name) = commands::split_path(&self.name);
+
+        let mut account = match self.note_type {
+            Some(ref note_type) => Account::new_secure_note(name, note_type),
+            None => Account::new(name, self.url.clone()),
+        };
+        account.move_to(folder);
+
+        account
+    }
+}
@@ -0,0 +1,13 @@
+use crate::{agent, store::Store};
+use anyhow::Error;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Agent {}
+
+impl Agent {
+    pub fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        agent::run(&store)
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{commands, store::Store};
+use anyhow::Error;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Duplicate {
+    /// The account's name, path (e.g. "Folder/Name") or ID.
+    account: String,
+    /// What to call the copy (defaults to the original's name).
+    name: Option<String>,
+}
+
+impl Duplicate {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let (vault, decryption_key) =
+            commands::unlock(&store, &session).await?;
+
+        let original = commands::find_account(&vault, &self.account)?;
+        // the copy stays in the same (possibly shared) folder, so it gets
+        // encrypted with the same key
+        let name = self.name.as_deref().unwrap_or(&original.name);
+        let mut copy = original.duplicate(name);
+
+        commands::save_account(
+            &store,
+            &session,
+            &vault,
+            &mut copy,
+            &decryption_key,
+        )
+        .await?;
+        println!("Added {} [id: {}]", copy.path(), &*copy.id);
+
+        Ok(())
+    }
+}
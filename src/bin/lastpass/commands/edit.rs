@@ -0,0 +1,62 @@
+use crate::{commands, store::Store, template};
+use anyhow::Error;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Edit {
+    /// The account's name, path (e.g. "Folder/Name") or ID.
+    account: String,
+    /// Read the edited template from stdin instead of opening an editor.
+    #[structopt(long)]
+    non_interactive: bool,
+}
+
+impl Edit {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let (vault, decryption_key) =
+            commands::unlock(&store, &session).await?;
+
+        let original = commands::find_account(&vault, &self.account)?;
+        let mut account = original.clone();
+        let edited = commands::edit_text(
+            &template::render(&account),
+            self.non_interactive,
+        )?;
+        template::apply(&edited, &mut account)?;
+
+        if account == *original {
+            println!("No changes were made.");
+            return Ok(());
+        }
+
+        let from = commands::account_share(&vault, original)?;
+        let to = vault.share_for_folder(&account.folder());
+
+        if commands::same_share(from, to) {
+            commands::save_account(
+                &store,
+                &session,
+                &vault,
+                &mut account,
+                &decryption_key,
+            )
+            .await?;
+        } else {
+            // the account needs to be re-encrypted with the new folder's key
+            commands::move_account(
+                &store,
+                &session,
+                &mut account,
+                &decryption_key,
+                from,
+                to,
+            )
+            .await?;
+        }
+        println!("Updated {} [id: {}]", account.path(), &*account.id);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{commands, store::Store};
+use anyhow::{Context, Error};
+use lastpass::Reference;
+use std::{io, process::Command};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Exec {
+    /// Set an environment variable to a value from the vault, using the
+    /// "path:field" syntax (e.g. "DB_PASS=prod/db:password").
+    #[structopt(
+        short = "e",
+        long = "env",
+        value_name = "NAME=REFERENCE",
+        parse(try_from_str = parse_variable),
+        number_of_values = 1
+    )]
+    variables: Vec<(String, Reference)>,
+    /// The program to run, and its arguments.
+    #[structopt(required = true, last = true)]
+    command: Vec<String>,
+}
+
+impl Exec {
+    pub async fn run(self) -> Result<(), Error> {
+        if cfg!(not(unix)) {
+            anyhow::bail!("lastpass exec is only supported on Unix");
+        }
+
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let vault = commands::load_vault(&store, &session).await?;
+
+        let mut command = Command::new(&self.command[0]);
+        command.args(&self.command[1..]);
+
+        for (name, reference) in &self.variables {
+            let value = reference.resolve(&vault).with_context(|| {
+                format!("Unable to resolve ${} ({})", name, reference)
+            })?;
+            command.env(name, value);
+        }
+
+        // replace ourselves with the child, so the secrets never pass through
+        // our stdout and the caller sees its exit code
+        let err = replace_process(&mut command);
+
+        Err(Error::from(err)
+            .context(format!("Unable to run \"{}\"", self.command[0])))
+    }
+}
+
+/// Run `command` in place of the current process, only returning if that
+/// fails.
+#[cfg(unix)]
+fn replace_process(command: &mut Command) -> io::Error {
+    use std::os::unix::process::CommandExt;
+
+    command.exec()
+}
+
+#[cfg(not(unix))]
+fn replace_process(_command: &mut Command) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "Replacing the current process is only supported on Unix",
+    )
+}
+
+fn parse_variable(src: &str) -> Result<(String, Reference), Error> {
+    let equals = src
+        .find('=')
+        .context("Expected something like NAME=path:field")?;
+    let (name, reference) = (&src[..equals], &src[equals + 1..]);
+
+    if name.is_empty() {
+        anyhow::bail!("The environment variable needs a name");
+    }
+
+    Ok((name.to_string(), reference.parse()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lastpass::Field;
+
+    #[test]
+    fn parse_environment_variables() {
+        let got = parse_variable("DB_PASS=prod/db:password").unwrap();
+
+        assert_eq!(
+            got,
+            (
+                String::from("DB_PASS"),
+                Reference::new("prod/db", Field::Password)
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_environment_variables() {
+        for src in &["DB_PASS", "=prod/db:password", "DB_PASS=", "X=prod/db:"] {
+            assert!(parse_variable(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn the_command_comes_after_a_double_dash() {
+        let got = Exec::from_iter_safe(&[
+            "exec",
+            "--env",
+            "DB_PASS=prod/db:password",
+            "-e",
+            "DB_USER=prod/db:username",
+            "--",
+            "./deploy.sh",
+            "--env",
+            "prod",
+        ])
+        .unwrap();
+
+        assert_eq!(got.variables.len(), 2);
+        assert_eq!(got.command, &["./deploy.sh", "--env", "prod"]);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{commands, store::Store};
+use anyhow::Error;
+use lastpass::{Account, PassphraseGenerator, PasswordGenerator, Vault};
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub struct Generate {
+    /// The account's name, path (e.g. "Folder/Name") or ID. A new account is
+    /// created if it doesn't exist.
+    account: String,
+    /// How many characters the password should have.
+    #[structopt(short, long, default_value = "20")]
+    length: usize,
+    /// Don't use punctuation.
+    #[structopt(long)]
+    no_symbols: bool,
+    /// Leave out characters which look alike (e.g. "0" and "O").
+    #[structopt(long)]
+    exclude_ambiguous: bool,
+    /// Alternate consonants and vowels so the password can be read aloud.
+    #[structopt(long, conflicts_with = "words")]
+    pronounceable: bool,
+    /// Generate a passphrase with this many random words instead.
+    #[structopt(long, value_name = "COUNT")]
+    words: Option<usize>,
+    /// Set the account's username.
+    #[structopt(long)]
+    username: Option<String>,
+    /// Set the account's URL.
+    #[structopt(long)]
+    url: Option<Url>,
+}
+
+impl Generate {
+    pub async fn run(self) -> Result<(), Error> {
+        let password = self.password()?;
+
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let (vault, decryption_key) =
+            commands::unlock(&store, &session).await?;
+
+        let mut account = match commands::lookup_account(&vault, &self.account)?
+        {
+            Some(existing) => existing.clone(),
+            None => self.new_account(&vault),
+        };
+
+        account.password = password;
+        if let Some(ref username) = self.username {
+            account.username = username.clone();
+        }
+        if let Some(ref url) = self.url {
+            account.url = url.clone();
+        }
+
+        commands::save_account(
+            &store,
+            &session,
+            &vault,
+            &mut account,
+            &decryption_key,
+        )
+        .await?;
+        println!("{}", account.password);
+
+        Ok(())
+    }
+
+    fn password(&self) -> Result<String, Error> {
+        if let Some(words) = self.words {
+            let generator = PassphraseGenerator {
+                words,
+                ..Default::default()
+            };
+            return Ok(generator.generate());
+        }
+
+        let generator = PasswordGenerator {
+            length: self.length,
+            symbols: !self.no_symbols,
+            exclude_ambiguous: self.exclude_ambiguous,
+            pronounceable: self.pronounceable,
+            ..Default::default()
+        };
+
+        Ok(generator.generate()?)
+    }
+
+    fn new_account(&self, vault: &Vault) -> Account {
+        let (folder, name) = commands::split_path(&self.account);
+        let url = self
+            .url
+            .clone()
+            .unwrap_or_else(|| Url::parse("https://example.com/").unwrap());
+
+        let mut account = Account::new(name, url);
+        account.move_to(folder);
+        account.share =
+            vault.share_for_folder(folder).map(|share| share.id.clone());
+
+        account
+    }
+}
@@ -0,0 +1,555 @@
+//! A helper for `git credential`, see `gitcredentials(7)`.
+//!
+//! Configure it with something like:
+//!
+//! ```console
+//! $ git config --global credential.helper '!lastpass git-credential --group Git'
+//! ```
+
+use crate::{commands, store::Store};
+use anyhow::{Context, Error};
+use lastpass::{endpoints, Account, Vault};
+use std::{
+    io::{self, BufRead},
+    str::FromStr,
+};
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub struct GitCredential {
+    /// The folder new credentials are saved to. Credentials are only stored
+    /// (or erased) when this is set.
+    #[structopt(long, env = "LASTPASS_GIT_GROUP")]
+    group: Option<String>,
+    /// What git wants us to do ("get", "store" or "erase").
+    operation: Operation,
+}
+
+impl GitCredential {
+    pub async fn run(self) -> Result<(), Error> {
+        let stdin = io::stdin();
+        let credential = Credential::parse(stdin.lock())?;
+
+        // git will call "store" after every successful login, so changing
+        // the vault is strictly opt-in
+        let group = match self.group {
+            Some(group) => group,
+            None if self.operation == Operation::Get => String::new(),
+            None => return Ok(()),
+        };
+
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let (vault, decryption_key) =
+            commands::unlock(&store, &session).await?;
+
+        match self.operation {
+            Operation::Get => {
+                if let Some(account) = credential.matches(&vault).first() {
+                    print!("{}", credential.response(account));
+                }
+            },
+            Operation::Store => {
+                if let Some(mut account) =
+                    credential.to_store(&vault, &group)?
+                {
+                    commands::save_account(
+                        &store,
+                        &session,
+                        &vault,
+                        &mut account,
+                        &decryption_key,
+                    )
+                    .await?;
+                }
+            },
+            Operation::Erase => {
+                let client = commands::client(Some(&session))?;
+                let rejected = credential.to_erase(&vault, &group);
+
+                for account in &rejected {
+                    let share = commands::account_share(&vault, account)?;
+                    commands::ensure_writable(share)?;
+                    endpoints::delete_account(
+                        &client,
+                        &session.host,
+                        &session.token,
+                        &account.id,
+                        share,
+                    )
+                    .await
+                    .with_context(|| {
+                        format!("Unable to delete {}", account.path())
+                    })?;
+                }
+
+                if !rejected.is_empty() {
+                    commands::sync_vault(&store, &client, &session, true)
+                        .await?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operation {
+    Get,
+    Store,
+    Erase,
+}
+
+impl FromStr for Operation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "get" => Ok(Operation::Get),
+            "store" => Ok(Operation::Store),
+            "erase" => Ok(Operation::Erase),
+            other => anyhow::bail!("Unknown operation, \"{}\"", other),
+        }
+    }
+}
+
+/// The attributes git sends us.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Credential {
+    protocol: String,
+    /// The hostname, including the port if it isn't the default.
+    host: String,
+    /// The repository's path, when `credential.useHttpPath` is set.
+    path: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl Credential {
+    /// Read `key=value` lines until a blank line or the end of input.
+    fn parse<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut credential = Credential::default();
+
+        for line in reader.lines() {
+            let line = line.context("Unable to read the credential")?;
+            if line.is_empty() {
+                break;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(equals) => (&line[..equals], &line[equals + 1..]),
+                None => anyhow::bail!("Expected a key=value line"),
+            };
+
+            match key {
+                "protocol" => credential.protocol = value.to_string(),
+                "host" => credential.host = value.to_string(),
+                "path" => credential.path = Some(value.to_string()),
+                "username" => credential.username = Some(value.to_string()),
+                "password" => credential.password = Some(value.to_string()),
+                "url" => credential.set_url(value)?,
+                // newer versions of git send things like capability[] and
+                // wwwauth[] which we don't care about
+                _ => {},
+            }
+        }
+
+        if credential.protocol.is_empty() || credential.host.is_empty() {
+            anyhow::bail!("git needs to tell us the protocol and host");
+        }
+
+        Ok(credential)
+    }
+
+    fn set_url(&mut self, raw: &str) -> Result<(), Error> {
+        let url =
+            Url::parse(raw).with_context(|| format!("Invalid URL: {}", raw))?;
+
+        self.protocol = url.scheme().to_string();
+        self.host = host_and_port(&url).unwrap_or_default();
+        let path = url.path().trim_start_matches('/');
+        if !path.is_empty() {
+            self.path = Some(path.to_string());
+        }
+        if !url.username().is_empty() {
+            self.username = Some(url.username().to_string());
+        }
+        if let Some(password) = url.password() {
+            self.password = Some(password.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Does this [`Account`] hold the credentials for the same site?
+    fn is_for(&self, account: &Account) -> bool {
+        let url = &account.url;
+        let same_host = matches!(
+            host_and_port(url),
+            Some(host) if host.eq_ignore_ascii_case(&self.host)
+        );
+
+        if account.is_group()
+            || account.is_secure_note()
+            || url.scheme() != self.protocol
+            || !same_host
+        {
+            return false;
+        }
+
+        // accounts without a path cover every repository on the host
+        let account_path = normalize_path(url.path());
+        match self.path {
+            Some(ref path) if !account_path.is_empty() => {
+                account_path == normalize_path(path)
+            },
+            _ => true,
+        }
+    }
+
+    fn has_username(&self, account: &Account) -> bool {
+        match self.username {
+            Some(ref username) => account.username == *username,
+            None => true,
+        }
+    }
+
+    /// The accounts which could be used to log in, best match first.
+    fn matches<'v>(&self, vault: &'v Vault) -> Vec<&'v Account> {
+        let mut matches: Vec<&Account> = vault
+            .accounts
+            .iter()
+            .filter(|account| {
+                self.is_for(account) && self.has_username(account)
+            })
+            .collect();
+
+        matches.sort_by_key(|account| (self.rank(account), account.path()));
+
+        matches
+    }
+
+    /// How closely an account's path matches, where lower is better.
+    ///
+    /// An account for this exact repository wins, followed by accounts for
+    /// the whole host. Accounts for other repositories are only used as a
+    /// last resort when git doesn't tell us the repository.
+    fn rank(&self, account: &Account) -> u8 {
+        let account_path = normalize_path(account.url.path());
+
+        match self.path {
+            _ if account_path.is_empty() => 1,
+            Some(ref path) if normalize_path(path) == account_path => 0,
+            _ => 2,
+        }
+    }
+
+    /// The lines to send back to git.
+    fn response(&self, account: &Account) -> String {
+        let mut response = String::new();
+
+        if !account.username.is_empty() {
+            response.push_str(&format!("username={}\n", account.username));
+        }
+        response.push_str(&format!("password={}\n", account.password));
+
+        response
+    }
+
+    /// The new or updated account to save when git tells us some
+    /// credentials worked, or `None` if there's nothing to do.
+    ///
+    /// New accounts inherit the shared folder `group` is inside, if any.
+    fn to_store(
+        &self,
+        vault: &Vault,
+        group: &str,
+    ) -> Result<Option<Account>, Error> {
+        let (username, password) = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => (username, password),
+            _ => return Ok(None),
+        };
+
+        let existing = self
+            .matches(vault)
+            .into_iter()
+            .find(|account| in_group(account, group));
+
+        let mut account = match existing {
+            Some(account) if account.password == *password => return Ok(None),
+            Some(account) => account.clone(),
+            None => {
+                let mut account = Account::new(&self.host, self.url()?);
+                account.move_to(group);
+                account.share =
+                    vault.share_for_folder(group).map(|share| share.id.clone());
+                account.username = username.clone();
+                account
+            },
+        };
+        account.password = password.clone();
+
+        Ok(Some(account))
+    }
+
+    /// The accounts to delete after git tells us some credentials were
+    /// rejected.
+    ///
+    /// To avoid deleting anything important, we only touch accounts in our
+    /// own group which have exactly the rejected username and password.
+    fn to_erase<'v>(&self, vault: &'v Vault, group: &str) -> Vec<&'v Account> {
+        let (username, password) = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => (username, password),
+            _ => return Vec::new(),
+        };
+
+        self.matches(vault)
+            .into_iter()
+            .filter(|account| {
+                in_group(account, group)
+                    && account.username == *username
+                    && account.password == *password
+            })
+            .collect()
+    }
+
+    fn url(&self) -> Result<Url, Error> {
+        let raw = format!(
+            "{}://{}/{}",
+            self.protocol,
+            self.host,
+            self.path.as_deref().unwrap_or_default()
+        );
+
+        Url::parse(&raw).with_context(|| format!("Invalid URL: {}", raw))
+    }
+}
+
+fn host_and_port(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+
+    match url.port() {
+        Some(port) => Some(format!("{}:{}", host, port)),
+        None => Some(host.to_string()),
+    }
+}
+
+fn normalize_path(path: &str) -> &str {
+    let path = path.trim_matches('/');
+    path.strip_suffix(".git").unwrap_or(path)
+}
+
+fn in_group(account: &Account, group: &str) -> bool {
+    account.folder() == group.replace('\\', "/").trim_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::tests::dummy_vault;
+
+    fn credential(src: &str) -> Credential {
+        Credential::parse(src.as_bytes()).unwrap()
+    }
+
+    fn vault_with(accounts: Vec<(&str, &str, &str, &str)>) -> Vault {
+        let mut vault = dummy_vault();
+
+        for (i, (group, url, username, password)) in
+            accounts.into_iter().enumerate()
+        {
+            let mut account = Account::new("git", Url::parse(url).unwrap());
+            account.id = format!("100{}", i).into();
+            account.move_to(group);
+            account.username = username.to_string();
+            account.password = password.to_string();
+            vault.accounts.push(account);
+        }
+
+        vault
+    }
+
+    #[test]
+    fn parse_what_git_sends() {
+        let got = credential(
+            "protocol=https\nhost=github.com\npath=rust-lang/rust.git\nusername=bob\ncapability[]=authtype\n\nignored=true\n",
+        );
+
+        assert_eq!(
+            got,
+            Credential {
+                protocol: String::from("https"),
+                host: String::from("github.com"),
+                path: Some(String::from("rust-lang/rust.git")),
+                username: Some(String::from("bob")),
+                password: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_a_url_attribute() {
+        let got =
+            credential("url=https://bob@git.example.com:8443/team/repo\n");
+
+        assert_eq!(got.protocol, "https");
+        assert_eq!(got.host, "git.example.com:8443");
+        assert_eq!(got.path.as_deref(), Some("team/repo"));
+        assert_eq!(got.username.as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn the_protocol_and_host_are_required() {
+        assert!(Credential::parse(&b"host=github.com\n"[..]).is_err());
+        assert!(Credential::parse(&b"protocol=https\n"[..]).is_err());
+        assert!(Credential::parse(&b"protocol\n"[..]).is_err());
+    }
+
+    #[test]
+    fn match_on_protocol_host_and_path() {
+        let vault = vault_with(vec![
+            ("Git", "https://github.com/", "bob", "host-wide"),
+            ("Git", "https://github.com/team/repo", "bob", "repo"),
+            ("Git", "http://github.com/", "bob", "insecure"),
+            ("Git", "https://git.example.com:8443/", "alice", "port"),
+        ]);
+        let password = |src: &str| {
+            credential(src)
+                .matches(&vault)
+                .first()
+                .map(|account| account.password.clone())
+        };
+
+        assert_eq!(
+            password("protocol=https\nhost=github.com\n").as_deref(),
+            Some("host-wide")
+        );
+        assert_eq!(
+            password("protocol=https\nhost=github.com\npath=team/repo.git\n")
+                .as_deref(),
+            Some("repo")
+        );
+        assert_eq!(
+            password("protocol=https\nhost=github.com\npath=other/repo\n")
+                .as_deref(),
+            Some("host-wide")
+        );
+        assert_eq!(
+            password("protocol=http\nhost=github.com\n").as_deref(),
+            Some("insecure")
+        );
+        assert_eq!(
+            password("protocol=https\nhost=git.example.com:8443\n").as_deref(),
+            Some("port")
+        );
+        assert_eq!(password("protocol=https\nhost=git.example.com\n"), None);
+        assert_eq!(
+            password("protocol=https\nhost=github.com\nusername=carol\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn respond_with_the_username_and_password() {
+        let vault =
+            vault_with(vec![("Git", "https://github.com/", "bob", "hunter2")]);
+        let credential = credential("protocol=https\nhost=github.com\n");
+
+        let got = credential.response(credential.matches(&vault)[0]);
+
+        assert_eq!(got, "username=bob\npassword=hunter2\n");
+    }
+
+    #[test]
+    fn store_new_credentials_in_the_group() {
+        let vault = dummy_vault();
+        let credential = credential(
+            "protocol=https\nhost=github.com\nusername=bob\npassword=hunter2\n",
+        );
+
+        let got = credential.to_store(&vault, "Git/").unwrap().unwrap();
+
+        assert_eq!(got.path(), "Git/github.com");
+        assert_eq!(got.url.as_str(), "https://github.com/");
+        assert_eq!(got.username, "bob");
+        assert_eq!(got.password, "hunter2");
+    }
+
+    #[test]
+    fn storing_updates_existing_credentials() {
+        let vault =
+            vault_with(vec![("Git", "https://github.com/", "bob", "old")]);
+        let unchanged = credential(
+            "protocol=https\nhost=github.com\nusername=bob\npassword=old\n",
+        );
+        let changed = credential(
+            "protocol=https\nhost=github.com\nusername=bob\npassword=new\n",
+        );
+
+        assert!(unchanged.to_store(&vault, "Git").unwrap().is_none());
+
+        let got = changed.to_store(&vault, "Git").unwrap().unwrap();
+        assert_eq!(got.id, lastpass::Id::from("1000"));
+        assert_eq!(got.password, "new");
+    }
+
+    #[test]
+    fn only_erase_rejected_credentials_from_our_group() {
+        let vault = vault_with(vec![
+            ("Git", "https://github.com/", "bob", "rejected"),
+            ("Work", "https://github.com/", "bob", "rejected"),
+            ("Git", "https://github.com/team/repo", "bob", "still-valid"),
+        ]);
+        let credential = credential(
+            "protocol=https\nhost=github.com\nusername=bob\npassword=rejected\n",
+        );
+
+        let got = credential.to_erase(&vault, "Git");
+
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].folder(), "Git");
+        assert_eq!(got[0].password, "rejected");
+    }
+
+    #[test]
+    fn credentials_in_shared_folders_keep_their_share() {
+        let mut vault = vault_with(vec![(
+            "Shared-Git",
+            "https://github.com/",
+            "bob",
+            "old",
+        )]);
+        vault.accounts.last_mut().unwrap().share = Some("42".into());
+        vault.shares.push(lastpass::Share::new(
+            lastpass::Id::from("42"),
+            String::from("Shared-Git"),
+            lastpass::DecryptionKey::generate(),
+            false,
+        ));
+        let rejected = credential(
+            "protocol=https\nhost=github.com\nusername=bob\npassword=old\n",
+        );
+        let changed = credential(
+            "protocol=https\nhost=github.com\nusername=bob\npassword=new\n",
+        );
+        let someone_else = credential(
+            "protocol=https\nhost=github.com\nusername=alice\npassword=new\n",
+        );
+
+        let new = someone_else
+            .to_store(&vault, "Shared-Git")
+            .unwrap()
+            .unwrap();
+        assert_eq!(new.share, Some(lastpass::Id::from("42")));
+
+        let updated = changed.to_store(&vault, "Shared-Git").unwrap().unwrap();
+        assert_eq!(updated.id, lastpass::Id::from("1000"));
+        assert_eq!(updated.share, Some(lastpass::Id::from("42")));
+
+        let erased = rejected.to_erase(&vault, "Shared-Git");
+        assert_eq!(erased.len(), 1);
+        assert!(erased[0].is_shared());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{
+    agent, commands,
+    store::{SavedSession, Store},
+};
+use anyhow::{Context, Error};
+use lastpass::endpoints::{self, LoginError, RetryPolicy};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Login {
+    /// Your LastPass username (normally an email address).
+    username: String,
+    /// The LastPass server's hostname.
+    #[structopt(long, default_value = "lastpass.com")]
+    host: String,
+    /// A trusted device ID, used to skip verifying new locations.
+    #[structopt(long)]
+    trusted_id: Option<String>,
+}
+
+impl Login {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let client = commands::client(None)?;
+
+        let iterations = endpoints::iterations(
+            &client,
+            &self.host,
+            &self.username,
+            &RetryPolicy::default(),
+        )
+        .await
+        .context("Unable to find out how many iterations to use")?;
+
+        let password = commands::prompt_password("Master Password: ")?;
+
+        let (session, decryption_key) = match endpoints::login_with_password(
+            &client,
+            &self.host,
+            &self.username,
+            &password,
+            iterations,
+            self.trusted_id.as_deref(),
+        )
+        .await
+        {
+            Ok(session) => session,
+            Err(LoginError::TwoFactorLoginRequired(two_factor)) => {
+                let providers: Vec<_> = two_factor.providers().collect();
+                anyhow::bail!(
+                    "Two-factor authentication ({}) isn't supported yet",
+                    providers.join(", ")
+                );
+            },
+            Err(e) => return Err(Error::from(e).context("Login failed")),
+        };
+
+        // make sure we don't keep an old user's vault around
+        agent::lock(&store)?;
+        store.clear()?;
+        let saved = SavedSession::new(&self.username, &self.host, &session);
+        store.save_session(&saved)?;
+        commands::sync_vault(&store, &client, &saved, true).await?;
+
+        // we already have the key, so there's no need to ask for the master
+        // password again on the next command
+        if let Err(e) = agent::start(&store, &saved, &decryption_key) {
+            log::warn!("Unable to start the agent: {}", e);
+        }
+
+        println!("Success: Logged in as {}.", self.username);
+
+        Ok(())
+    }
+}
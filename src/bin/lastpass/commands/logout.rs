@@ -0,0 +1,29 @@
+use crate::{agent, commands, store::Store};
+use anyhow::Error;
+use lastpass::endpoints;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Logout {}
+
+impl Logout {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let client = commands::client(Some(&session))?;
+
+        // the local data gets removed regardless, so there's no point
+        // aborting if the server's session has already expired
+        if let Err(e) =
+            endpoints::logout(&client, &session.host, &session.token).await
+        {
+            log::warn!("Unable to end the session on the server: {}", e);
+        }
+
+        agent::lock(&store)?;
+        store.clear()?;
+        println!("Log out: complete.");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    commands,
+    format::{self, Format},
+    store::Store,
+};
+use anyhow::{Context, Error};
+use lastpass::{Account, Folder};
+use std::io::Write;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Ls {
+    /// Only list the accounts in this folder.
+    folder: Option<String>,
+    /// Show when each account was last modified and used, its username and
+    /// how many attachments it has.
+    #[structopt(short, long)]
+    long: bool,
+    /// Show the folders as an indented tree.
+    #[structopt(short, long)]
+    tree: bool,
+    /// How to print each account (e.g. "%aN [id: %ai]"), see the README for
+    /// the available placeholders.
+    #[structopt(long, conflicts_with = "long")]
+    format: Option<Format>,
+}
+
+impl Ls {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let vault = commands::load_vault(&store, &session).await?;
+
+        let root = vault.folders();
+        let folder = match self.folder {
+            Some(ref path) => root
+                .get(path)
+                .with_context(|| format!("No such folder, \"{}\"", path))?,
+            None => &root,
+        };
+
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+
+        for line in self.lines(folder) {
+            writeln!(stdout, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    fn lines(&self, folder: &Folder<'_>) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if self.tree {
+            self.tree_lines(folder, 0, &mut lines);
+        } else {
+            for folder in folder.walk() {
+                for account in sorted(folder) {
+                    lines.push(self.describe(account, &account.path()));
+                }
+            }
+        }
+
+        lines
+    }
+
+    fn tree_lines(
+        &self,
+        folder: &Folder<'_>,
+        depth: usize,
+        lines: &mut Vec<String>,
+    ) {
+        let indent = "    ".repeat(depth);
+
+        for account in sorted(folder) {
+            lines.push(format!(
+                "{}{}",
+                indent,
+                self.describe(account, &account.name)
+            ));
+        }
+
+        for child in &folder.folders {
+            lines.push(format!("{}{}/", indent, child.name));
+            self.tree_lines(child, depth + 1, lines);
+        }
+    }
+
+    fn describe(&self, account: &Account, name: &str) -> String {
+        if let Some(ref format) = self.format {
+            return format.render(account);
+        }
+
+        let mut line = format!("{} [id: {}]", name, &*account.id);
+
+        if self.long {
+            line = format!(
+                "{} {} {} [username: {}] [attachments: {}]",
+                format::timestamp(&account.last_modified),
+                format::timestamp(&account.last_touch),
+                line,
+                account.username,
+                account.attachments.len()
+            );
+        }
+
+        line
+    }
+}
+
+fn sorted<'a>(folder: &Folder<'a>) -> Vec<&'a Account> {
+    let mut accounts = folder.accounts.clone();
+    accounts.sort_by(|left, right| left.name.cmp(&right.name));
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::tests::dummy_vault;
+
+    fn ls(args: &[&str]) -> Vec<String> {
+        let vault = dummy_vault();
+        let root = vault.folders();
+        let ls =
+            Ls::from_iter(std::iter::once("ls").chain(args.iter().copied()));
+
+        let folder = match ls.folder {
+            Some(ref path) => root.get(path).unwrap(),
+            None => &root,
+        };
+
+        ls.lines(folder)
+    }
+
+    #[test]
+    fn list_everything() {
+        let got = ls(&[]);
+
+        assert_eq!(
+            got,
+            vec![
+                "Another Password [id: 8852885818375729232]",
+                "My Secure Note [id: 533903346832032070]",
+                "Some Folder/My Address [id: 5211400216940069976]",
+                "Some Folder/Nested/Example password without folder [id: 5496230974130180673]",
+            ]
+        );
+    }
+
+    #[test]
+    fn list_a_folder_as_a_tree() {
+        let got = ls(&["--tree", "Some Folder"]);
+
+        assert_eq!(
+            got,
+            vec![
+                "My Address [id: 5211400216940069976]",
+                "Nested/",
+                "    Example password without folder [id: 5496230974130180673]",
+            ]
+        );
+    }
+
+    #[test]
+    fn long_listing() {
+        let got = ls(&["--long", "Some Folder/Nested"]);
+
+        assert_eq!(
+            got,
+            vec![
+                "2020-04-12 18:53 2020-04-12 10:53 Some Folder/Nested/Example password without folder [id: 5496230974130180673] [username: username] [attachments: 0]",
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_format() {
+        let got = ls(&["--format", "%an|%au|%ac"]);
+
+        assert_eq!(
+            got,
+            vec![
+                "Another Password|user|0",
+                "My Secure Note||1",
+                "My Address||0",
+                "Example password without folder|username|0",
+            ]
+        );
+    }
+}
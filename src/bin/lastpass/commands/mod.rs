@@ -0,0 +1,417 @@
+//! The `lastpass` subcommands and the plumbing they share.
+
+mod add;
+mod agent;
+mod duplicate;
+mod edit;
+mod exec;
+mod generate;
+mod git_credential;
+mod login;
+mod logout;
+mod ls;
+mod mv;
+mod render;
+mod rm;
+mod show;
+mod status;
+mod sync;
+
+pub use add::Add;
+pub use agent::Agent;
+pub use duplicate::Duplicate;
+pub use edit::Edit;
+pub use exec::Exec;
+pub use generate::Generate;
+pub use git_credential::GitCredential;
+pub use login::Login;
+pub use logout::Logout;
+pub use ls::Ls;
+pub use mv::Mv;
+pub use render::Render;
+pub use rm::Rm;
+pub use show::Show;
+pub use status::Status;
+pub use sync::Sync;
+
+use crate::{
+    editor,
+    store::{SavedSession, Store},
+};
+use anyhow::{Context, Error};
+use lastpass::{
+    endpoints, endpoints::RetryPolicy, Account, DecryptionKey, Field,
+    Reference, ResolveError, Share, Vault,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, COOKIE},
+    Client,
+};
+use std::io::{BufRead, Read, Write};
+
+/// Create a HTTP client for talking to LastPass, reusing the saved session's
+/// cookie if we have one.
+pub fn client(session: Option<&SavedSession>) -> Result<Client, Error> {
+    let mut headers = HeaderMap::new();
+
+    if let Some(session) = session {
+        let cookie = format!("PHPSESSID={}", session.session_id);
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_str(&cookie)
+                .context("The saved session ID is invalid")?,
+        );
+    }
+
+    let client = Client::builder()
+        .user_agent(lastpass::DEFAULT_USER_AGENT)
+        .cookie_store(true)
+        .default_headers(headers)
+        .build()?;
+
+    Ok(client)
+}
+
+/// Get the saved session, complaining if the user isn't logged in.
+pub fn require_session(store: &Store) -> Result<SavedSession, Error> {
+    store
+        .session()?
+        .context("Not logged in, try `lastpass login`")
+}
+
+/// Download a new copy of the vault if the cached copy is out of date (or
+/// `force` is set), returning whether anything was downloaded.
+pub async fn sync_vault(
+    store: &Store,
+    client: &Client,
+    session: &SavedSession,
+    force: bool,
+) -> Result<bool, Error> {
+    let retry = RetryPolicy::default();
+
+    let version = endpoints::get_vault_version(client, &session.host, &retry)
+        .await
+        .context("Unable to check the vault version")?;
+
+    if !force {
+        if let Some((cached_version, _)) = store.vault()? {
+            if cached_version == version {
+                log::debug!("The cached vault (v{}) is up to date", version);
+                return Ok(false);
+            }
+        }
+    }
+
+    log::debug!("Downloading version {} of the vault", version);
+    let blob = endpoints::get_vault_blob(client, &session.host, &retry)
+        .await
+        .context("Unable to download the vault")?;
+    store.save_vault(version, &blob)?;
+
+    Ok(true)
+}
+
+/// Load the cached vault (downloading it if necessary) and decrypt it using
+/// the user's master password.
+pub async fn load_vault(
+    store: &Store,
+    session: &SavedSession,
+) -> Result<Vault, Error> {
+    unlock(store, session).await.map(|(vault, _)| vault)
+}
+
+/// Like [`load_vault()`], but also returns the key needed to make changes.
+pub async fn unlock(
+    store: &Store,
+    session: &SavedSession,
+) -> Result<(Vault, DecryptionKey), Error> {
+    let blob = match store.vault()? {
+        Some((_, blob)) => blob,
+        None => {
+            let client = client(Some(session))?;
+            sync_vault(store, &client, session, true).await?;
+            store.vault()?.context("The vault wasn't saved")?.1
+        },
+    };
+
+    if let Some(decryption_key) = crate::agent::decryption_key(store, session) {
+        match Vault::parse(&blob, &decryption_key, &session.private_key()?) {
+            Ok(vault) => return Ok((vault, decryption_key)),
+            Err(e) => {
+                // the agent has a stale key (e.g. the master password was
+                // changed), so stop it and ask for the password instead
+                log::warn!(
+                    "Unable to decrypt the vault using the agent's key: {}",
+                    e
+                );
+                if let Err(e) = crate::agent::lock(store) {
+                    log::warn!("Unable to stop the agent: {}", e);
+                }
+            },
+        }
+    }
+
+    let password = prompt_password("Master Password: ")?;
+    let (_, decryption_key) = lastpass::derive_keys_async(
+        &session.username,
+        &password,
+        session.iterations,
+    )
+    .await;
+
+    let vault = Vault::parse(&blob, &decryption_key, &session.private_key()?)
+        .context(
+        "Unable to decrypt the vault, is your master password correct?",
+    )?;
+
+    if let Err(e) = crate::agent::start(store, session, &decryption_key) {
+        log::warn!("Unable to start the agent: {}", e);
+    }
+
+    Ok((vault, decryption_key))
+}
+
+/// Upload an account's details, then refresh the cached vault so it includes
+/// the change.
+///
+/// Accounts in a shared folder are encrypted with that folder's key.
+pub async fn save_account(
+    store: &Store,
+    session: &SavedSession,
+    vault: &Vault,
+    account: &mut Account,
+    decryption_key: &DecryptionKey,
+) -> Result<(), Error> {
+    let client = client(Some(session))?;
+    let share = account_share(vault, account)?;
+    ensure_writable(share)?;
+
+    endpoints::save_account(
+        &client,
+        &session.host,
+        &session.token,
+        account,
+        decryption_key,
+        share,
+    )
+    .await
+    .with_context(|| format!("Unable to save \"{}\"", account.path()))?;
+
+    sync_vault(store, &client, session, true).await?;
+
+    Ok(())
+}
+
+/// Move an account into, out of or between shared folders, re-encrypting it
+/// with the destination's key, then refresh the cached vault.
+pub async fn move_account(
+    store: &Store,
+    session: &SavedSession,
+    account: &mut Account,
+    decryption_key: &DecryptionKey,
+    from: Option<&Share>,
+    to: Option<&Share>,
+) -> Result<(), Error> {
+    ensure_writable(from)?;
+    ensure_writable(to)?;
+    let client = client(Some(session))?;
+
+    endpoints::move_account(
+        &client,
+        &session.host,
+        &session.token,
+        account,
+        decryption_key,
+        from,
+        to,
+    )
+    .await
+    .with_context(|| format!("Unable to move \"{}\"", account.path()))?;
+
+    sync_vault(store, &client, session, true).await?;
+
+    Ok(())
+}
+
+/// The shared folder an account is in, if any.
+pub fn account_share<'v>(
+    vault: &'v Vault,
+    account: &Account,
+) -> Result<Option<&'v Share>, Error> {
+    match account.share {
+        Some(ref id) => vault
+            .get_share_by_id(id)
+            .map(Some)
+            .context("The account's shared folder no longer exists"),
+        None => Ok(None),
+    }
+}
+
+/// Are both accounts encrypted with the same key (i.e. in the same shared
+/// folder, or neither in one)?
+pub fn same_share(first: Option<&Share>, second: Option<&Share>) -> bool {
+    first.map(|share| &share.id) == second.map(|share| &share.id)
+}
+
+/// Complain if the user may only read a shared folder's accounts.
+pub fn ensure_writable(share: Option<&Share>) -> Result<(), Error> {
+    match share {
+        Some(share) if share.readonly => {
+            anyhow::bail!("The \"{}\" shared folder is read-only", share.name)
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Let the user edit some text, either in their editor or (when
+/// `non_interactive` is set) by reading the edited version from stdin.
+pub fn edit_text(text: &str, non_interactive: bool) -> Result<String, Error> {
+    if non_interactive {
+        let mut buffer = String::new();
+        std::io::stdin()
+            .read_to_string(&mut buffer)
+            .context("Unable to read from stdin")?;
+        Ok(buffer)
+    } else {
+        editor::edit(text)
+    }
+}
+
+/// Ask the user a yes/no question, defaulting to "no".
+pub fn confirm(question: &str) -> Result<bool, Error> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("Unable to read from stdin")?;
+
+    Ok(is_yes(&answer))
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+pub fn prompt_password(prompt: &str) -> Result<String, Error> {
+    rpassword::read_password_from_tty(Some(prompt))
+        .context("Unable to read the password")
+}
+
+/// Split an entry's path (e.g. `"Work/Email"`) into its folder and name.
+pub fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind(['/', '\\']) {
+        Some(index) => (&path[..index], &path[index + 1..]),
+        None => ("", path),
+    }
+}
+
+/// Find the account a user is referring to, either by [`lastpass::Id`], full
+/// path or name.
+pub fn find_account<'v>(
+    vault: &'v Vault,
+    query: &str,
+) -> Result<&'v Account, Error> {
+    lookup_account(vault, query)?
+        .with_context(|| format!("Could not find \"{}\"", query))
+}
+
+/// Like [`find_account()`], except it isn't an error when nothing matches.
+///
+/// This follows the same rules as [`Reference::account()`], so an account
+/// means the same thing everywhere.
+pub fn lookup_account<'v>(
+    vault: &'v Vault,
+    query: &str,
+) -> Result<Option<&'v Account>, Error> {
+    let reference = Reference::new(query, Field::Password);
+
+    match reference.account(vault) {
+        Ok(account) => Ok(Some(account)),
+        Err(ResolveError::NotFound { .. }) => Ok(None),
+        Err(ResolveError::Ambiguous { .. }) => {
+            let names: Vec<_> = reference
+                .candidates(vault)
+                .iter()
+                .map(|account| {
+                    format!("{} [id: {}]", account.path(), &*account.id)
+                })
+                .collect();
+            anyhow::bail!(
+                "Multiple accounts match \"{}\":\n{}",
+                query,
+                names.join("\n")
+            )
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lastpass::{DecryptionKey, PrivateKey};
+
+    /// The vault from the dummy account used by the library's tests.
+    pub(crate) fn dummy_vault() -> Vault {
+        let raw = include_bytes!("../../../vault_from_dummy_account.bin");
+        let decryption_key = DecryptionKey::from_hex(
+            "08c9bb2d9b48b39efb774e3fef32a38cb0d46c5c6c75f7f9d65259bfd374e120",
+        )
+        .unwrap();
+
+        Vault::parse(raw, &decryption_key, &PrivateKey::new(Vec::new()))
+            .unwrap()
+    }
+
+    #[test]
+    fn find_accounts_by_id_path_or_name() {
+        let vault = dummy_vault();
+
+        for query in &[
+            "8852885818375729232",
+            "Another Password",
+            "Some Folder/My Address",
+            r"Some Folder\My Address",
+        ] {
+            assert!(find_account(&vault, query).is_ok(), "{}", query);
+        }
+
+        assert!(find_account(&vault, "Missing").is_err());
+        // folder placeholders aren't accounts
+        assert!(find_account(&vault, "Some Folder/").is_err());
+    }
+
+    #[test]
+    fn split_paths_into_folder_and_name() {
+        assert_eq!(split_path("Email"), ("", "Email"));
+        assert_eq!(split_path("Work/Servers/Prod"), ("Work/Servers", "Prod"));
+        assert_eq!(split_path(r"Work\Email"), ("Work", "Email"));
+    }
+
+    #[test]
+    fn only_an_explicit_yes_confirms() {
+        for answer in &["y\n", "Y", " yes \n"] {
+            assert!(is_yes(answer), "{:?}", answer);
+        }
+        for answer in &["", "\n", "n", "no", "yeah"] {
+            assert!(!is_yes(answer), "{:?}", answer);
+        }
+    }
+
+    #[test]
+    fn ambiguous_matches_list_the_candidates() {
+        let mut vault = dummy_vault();
+        let mut duplicate = vault.accounts[1].clone();
+        duplicate.id = "1234".into();
+        vault.accounts.push(duplicate);
+
+        let err = find_account(&vault, "Another Password").unwrap_err();
+
+        let message = err.to_string();
+        assert!(message.contains("[id: 8852885818375729232]"));
+        assert!(message.contains("[id: 1234]"));
+    }
+}
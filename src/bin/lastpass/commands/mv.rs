@@ -0,0 +1,173 @@
+use crate::{commands, store::Store};
+use anyhow::Error;
+use lastpass::{Account, Share, Vault};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Mv {
+    /// The account's name, path (e.g. "Folder/Name") or ID.
+    account: String,
+    /// The folder to move it to (e.g. "Work/Servers"), or "" for the top
+    /// level.
+    folder: String,
+}
+
+impl Mv {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let (vault, decryption_key) =
+            commands::unlock(&store, &session).await?;
+
+        let mut account =
+            commands::find_account(&vault, &self.account)?.clone();
+        let from = account.path();
+        let (from_share, to_share) =
+            move_account(&vault, &mut account, &self.folder)?;
+
+        if account.path() == from {
+            println!("{} is already in that folder.", from);
+            return Ok(());
+        }
+
+        if commands::same_share(from_share, to_share) {
+            commands::save_account(
+                &store,
+                &session,
+                &vault,
+                &mut account,
+                &decryption_key,
+            )
+            .await?;
+        } else {
+            // the account needs to be re-encrypted with the new folder's key
+            commands::move_account(
+                &store,
+                &session,
+                &mut account,
+                &decryption_key,
+                from_share,
+                to_share,
+            )
+            .await?;
+        }
+        println!("Moved {} to {}", from, account.path());
+
+        Ok(())
+    }
+}
+
+/// Move an account to another folder, returning the shared folders it is
+/// moving from and to (if any).
+fn move_account<'v>(
+    vault: &'v Vault,
+    account: &mut Account,
+    folder: &str,
+) -> Result<(Option<&'v Share>, Option<&'v Share>), Error> {
+    let from = commands::account_share(vault, account)?;
+    let to = vault.share_for_folder(folder);
+
+    commands::ensure_writable(from)?;
+    commands::ensure_writable(to)?;
+    account.move_to(folder);
+
+    Ok((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::tests::dummy_vault;
+    use lastpass::{DecryptionKey, Id};
+
+    fn vault_with_shares() -> Vault {
+        let mut vault = dummy_vault();
+        vault.shares.push(Share::new(
+            Id::from("42"),
+            String::from("Shared-Team"),
+            DecryptionKey::generate(),
+            false,
+        ));
+        vault.shares.push(Share::new(
+            Id::from("43"),
+            String::from("Shared-Readonly"),
+            DecryptionKey::generate(),
+            true,
+        ));
+        vault
+    }
+
+    fn another_password(vault: &Vault) -> Account {
+        vault
+            .find_by_name("Another Password")
+            .next()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn move_to_another_folder() {
+        let vault = dummy_vault();
+        let mut account = another_password(&vault);
+
+        let (from, to) =
+            move_account(&vault, &mut account, "Work/Servers").unwrap();
+
+        assert_eq!(account.path(), "Work/Servers/Another Password");
+        assert!(from.is_none() && to.is_none());
+    }
+
+    #[test]
+    fn move_into_a_shared_folder() {
+        let vault = vault_with_shares();
+        let mut account = another_password(&vault);
+
+        let (from, to) =
+            move_account(&vault, &mut account, "Shared-Team/Servers").unwrap();
+
+        assert!(from.is_none());
+        assert_eq!(to.unwrap().id, Id::from("42"));
+        assert!(!commands::same_share(from, to));
+    }
+
+    #[test]
+    fn move_out_of_a_shared_folder() {
+        let vault = vault_with_shares();
+        let mut account = another_password(&vault);
+        account.move_to("Shared-Team");
+        account.share = Some(Id::from("42"));
+
+        let (from, to) =
+            move_account(&vault, &mut account, "Personal").unwrap();
+
+        assert_eq!(from.unwrap().id, Id::from("42"));
+        assert!(to.is_none());
+        assert_eq!(account.path(), "Personal/Another Password");
+    }
+
+    #[test]
+    fn move_within_a_shared_folder() {
+        let vault = vault_with_shares();
+        let mut account = another_password(&vault);
+        account.move_to("Shared-Team");
+        account.share = Some(Id::from("42"));
+
+        let (from, to) =
+            move_account(&vault, &mut account, "Shared-Team/Servers").unwrap();
+
+        assert!(commands::same_share(from, to));
+    }
+
+    #[test]
+    fn read_only_shared_folders_are_rejected() {
+        let vault = vault_with_shares();
+        let mut account = another_password(&vault);
+        let original_folder = account.folder();
+
+        assert!(move_account(&vault, &mut account, "Shared-Readonly").is_err());
+        assert_eq!(account.folder(), original_folder);
+
+        account.share = Some(Id::from("43"));
+        assert!(move_account(&vault, &mut account, "").is_err());
+    }
+}
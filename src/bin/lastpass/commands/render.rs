@@ -0,0 +1,73 @@
+use crate::{
+    commands,
+    store::{self, Store},
+};
+use anyhow::{Context, Error};
+use lastpass::{endpoints, endpoints::RetryPolicy, Template};
+use std::{collections::HashMap, fs, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Render {
+    /// A file containing references like
+    /// {{ lastpass "prod/db" "password" }}.
+    #[structopt(parse(from_os_str))]
+    template: PathBuf,
+    /// Where to save the result (only readable by you). Defaults to stdout.
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl Render {
+    pub async fn run(self) -> Result<(), Error> {
+        let src = fs::read_to_string(&self.template).with_context(|| {
+            format!("Unable to read \"{}\"", self.template.display())
+        })?;
+        let template: Template = src.parse().with_context(|| {
+            format!("Unable to parse \"{}\"", self.template.display())
+        })?;
+
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let (vault, decryption_key) =
+            commands::unlock(&store, &session).await?;
+
+        let client = commands::client(Some(&session))?;
+        let mut attachments = HashMap::new();
+
+        for attachment in template.attachments(&vault, &decryption_key)? {
+            let owner = vault
+                .get_account_by_id(&attachment.parent)
+                .context("The attachment's account has disappeared")?;
+            let attachment_key = owner.attachment_key(&decryption_key)?;
+            let contents = endpoints::load_attachment(
+                &client,
+                &session.host,
+                &session.token,
+                &attachment.storage_key,
+                &attachment_key,
+                &RetryPolicy::default(),
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Unable to download an attachment from {}",
+                    owner.path()
+                )
+            })?;
+            attachments.insert(attachment.id.clone(), contents);
+        }
+
+        let rendered =
+            template.render(&vault, &decryption_key, &attachments)?;
+
+        match self.output {
+            Some(ref output) => {
+                store::write_private(output, rendered.as_bytes())?
+            },
+            None => print!("{}", rendered),
+        }
+
+        Ok(())
+    }
+}
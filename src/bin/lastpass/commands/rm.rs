@@ -0,0 +1,49 @@
+use crate::{commands, store::Store};
+use anyhow::{Context, Error};
+use lastpass::endpoints;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Rm {
+    /// The account's name, path (e.g. "Folder/Name") or ID.
+    account: String,
+    /// Don't ask for confirmation.
+    #[structopt(short, long)]
+    force: bool,
+}
+
+impl Rm {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let vault = commands::load_vault(&store, &session).await?;
+
+        let account = commands::find_account(&vault, &self.account)?;
+        let description = format!("{} [id: {}]", account.path(), &*account.id);
+        let share = commands::account_share(&vault, account)?;
+        commands::ensure_writable(share)?;
+
+        if !self.force
+            && !commands::confirm(&format!("Delete {}?", description))?
+        {
+            println!("Nothing was deleted.");
+            return Ok(());
+        }
+
+        let client = commands::client(Some(&session))?;
+        endpoints::delete_account(
+            &client,
+            &session.host,
+            &session.token,
+            &account.id,
+            share,
+        )
+        .await
+        .with_context(|| format!("Unable to delete {}", description))?;
+        commands::sync_vault(&store, &client, &session, true).await?;
+
+        println!("Deleted {}", description);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{commands, store::Store};
+use anyhow::{Context, Error};
+use lastpass::{Account, Id};
+use serde_derive::Serialize;
+use structopt::{clap::ArgGroup, StructOpt};
+
+#[derive(Debug, StructOpt)]
+#[structopt(group = ArgGroup::with_name("output"))]
+pub struct Show {
+    /// The account's name, path (e.g. "Folder/Name") or ID.
+    account: String,
+    /// Only print the password.
+    #[structopt(long, group = "output")]
+    password: bool,
+    /// Only print the username.
+    #[structopt(long, group = "output")]
+    username: bool,
+    /// Only print the URL.
+    #[structopt(long, group = "output")]
+    url: bool,
+    /// Only print the notes.
+    #[structopt(long, group = "output")]
+    notes: bool,
+    /// Only print a secure note's field (e.g. "Hostname").
+    #[structopt(long, group = "output", value_name = "NAME")]
+    field: Option<String>,
+    /// Print the entry as JSON.
+    #[structopt(long, group = "output")]
+    json: bool,
+}
+
+impl Show {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let vault = commands::load_vault(&store, &session).await?;
+
+        let account = commands::find_account(&vault, &self.account)?;
+
+        if self.json {
+            let json = serde_json::to_string_pretty(&[Entry::new(account)])?;
+            println!("{}", json);
+        } else if let Some(value) = self.selected_field(account)? {
+            println!("{}", value);
+        } else {
+            print!("{}", details(account));
+        }
+
+        Ok(())
+    }
+
+    /// The single field the user asked for, if any.
+    fn selected_field<'a>(
+        &self,
+        account: &'a Account,
+    ) -> Result<Option<&'a str>, Error> {
+        let value = if self.password {
+            account.password.as_str()
+        } else if self.username {
+            account.username.as_str()
+        } else if self.url {
+            account.url.as_str()
+        } else if self.notes {
+            account.note.as_str()
+        } else if let Some(ref name) = self.field {
+            account.note_field(name).with_context(|| {
+                format!(
+                    "\"{}\" doesn't have a \"{}\" field",
+                    account.name, name
+                )
+            })?
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(value))
+    }
+}
+
+/// Format an account the same way `lpass show` does, skipping empty fields.
+fn details(account: &Account) -> String {
+    let mut buffer = format!("{} [id: {}]\n", account.path(), &*account.id);
+
+    let fields = account.note_fields();
+
+    if fields.is_empty() {
+        let url = if account.is_secure_note() {
+            ""
+        } else {
+            account.url.as_str()
+        };
+        let fields = [
+            ("Username", account.username.as_str()),
+            ("Password", account.password.as_str()),
+            ("URL", url),
+            ("Notes", account.note.as_str()),
+        ];
+
+        for (name, value) in &fields {
+            if !value.is_empty() {
+                buffer.push_str(&format!("{}: {}\n", name, value));
+            }
+        }
+    } else {
+        for (name, value) in fields {
+            if !value.is_empty() {
+                buffer.push_str(&format!("{}: {}\n", name, value));
+            }
+        }
+    }
+
+    buffer
+}
+
+/// The JSON representation of an [`Account`], mirroring `lpass show --json`.
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    id: &'a Id,
+    name: &'a str,
+    fullname: String,
+    username: &'a str,
+    password: &'a str,
+    last_modified_gmt: &'a str,
+    last_touch: &'a str,
+    group: String,
+    url: &'a str,
+    note: &'a str,
+    note_type: &'a str,
+    favourite: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<Field<'a>>,
+    attachments: Vec<AttachmentEntry<'a>>,
+}
+
+impl<'a> Entry<'a> {
+    fn new(account: &'a Account) -> Self {
+        Entry {
+            id: &account.id,
+            name: &account.name,
+            fullname: account.path(),
+            username: &account.username,
+            password: &account.password,
+            last_modified_gmt: &account.last_modified,
+            last_touch: &account.last_touch,
+            group: account.folder(),
+            url: account.url.as_str(),
+            note: &account.note,
+            note_type: &account.note_type,
+            favourite: account.favourite,
+            fields: account
+                .note_fields()
+                .into_iter()
+                .map(|(name, value)| Field { name, value })
+                .collect(),
+            attachments: account
+                .attachments
+                .iter()
+                .map(|attachment| AttachmentEntry {
+                    id: &attachment.id,
+                    mime_type: &attachment.mime_type,
+                    size: attachment.size,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Field<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct AttachmentEntry<'a> {
+    id: &'a Id,
+    mime_type: &'a str,
+    size: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::tests::dummy_vault;
+
+    #[test]
+    fn show_a_password() {
+        let vault = dummy_vault();
+        let account =
+            commands::find_account(&vault, "Another Password").unwrap();
+
+        let got = details(account);
+
+        assert_eq!(
+            got,
+            "Another Password [id: 8852885818375729232]\nUsername: user\nPassword: My Super Secret Password!!1!\nURL: https://google.com/\n"
+        );
+    }
+
+    #[test]
+    fn secure_notes_show_their_fields() {
+        let vault = dummy_vault();
+        let account = commands::find_account(&vault, "My Address").unwrap();
+
+        let got = details(account);
+
+        assert!(got.starts_with("Some Folder/My Address [id: 5211400216940069976]\nNoteType: Address\n"));
+        assert!(got.contains("\nCity / Town: Springfield\n"));
+        // empty fields are skipped
+        assert!(!got.contains("County"));
+    }
+
+    #[test]
+    fn select_a_single_field() {
+        let vault = dummy_vault();
+        let account = commands::find_account(&vault, "My Address").unwrap();
+        let show =
+            Show::from_iter(&["show", "My Address", "--field", "company"]);
+
+        let got = show.selected_field(account).unwrap();
+
+        assert_eq!(got, Some("Acme Corporation"));
+    }
+
+    #[test]
+    fn json_output() {
+        let vault = dummy_vault();
+        let account =
+            commands::find_account(&vault, "Another Password").unwrap();
+
+        let got = serde_json::to_value(Entry::new(account)).unwrap();
+
+        assert_eq!(got["id"], "8852885818375729232");
+        assert_eq!(got["fullname"], "Another Password");
+        assert_eq!(got["password"], "My Super Secret Password!!1!");
+        assert!(got.get("fields").is_none());
+    }
+}
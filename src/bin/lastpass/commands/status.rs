@@ -0,0 +1,33 @@
+use crate::store::Store;
+use anyhow::Error;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Status {
+    /// Don't print anything, just set the exit code.
+    #[structopt(short, long)]
+    quiet: bool,
+}
+
+impl Status {
+    pub fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+
+        match store.session()? {
+            Some(session) => {
+                if !self.quiet {
+                    println!("Logged in as {}.", session.username);
+                }
+
+                Ok(())
+            },
+            None => {
+                if !self.quiet {
+                    println!("Not logged in.");
+                }
+
+                std::process::exit(1);
+            },
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{commands, store::Store};
+use anyhow::Error;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Sync {
+    /// Download the vault even if the cached copy is up to date.
+    #[structopt(short, long)]
+    force: bool,
+}
+
+impl Sync {
+    pub async fn run(self) -> Result<(), Error> {
+        let store = Store::open()?;
+        let session = commands::require_session(&store)?;
+        let client = commands::client(Some(&session))?;
+
+        commands::sync_vault(&store, &client, &session, self.force).await?;
+
+        Ok(())
+    }
+}
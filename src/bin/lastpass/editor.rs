@@ -0,0 +1,208 @@
+//! Letting the user edit sensitive text in their `$EDITOR`.
+//!
+//! The text is written to a private directory on a memory-backed (`tmpfs`)
+//! filesystem, so the plaintext never touches the disk. If no such directory
+//! can be found we refuse to continue.
+
+use anyhow::{Context, Error};
+use rand::Rng;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Filesystems which only ever live in memory.
+const MEMORY_FILESYSTEMS: &[&str] = &["tmpfs", "ramfs"];
+
+/// Open `text` in the user's editor, returning the edited version.
+pub fn edit(text: &str) -> Result<String, Error> {
+    let parent = secure_tmpdir()?;
+    let dir = parent.join(format!(
+        "lastpass-{}-{:x}",
+        std::process::id(),
+        rand::thread_rng().gen::<u64>()
+    ));
+    create_private_dir(&dir)?;
+
+    let result = edit_in(&dir, text);
+
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        log::warn!("Unable to clean up \"{}\": {}", dir.display(), e);
+    }
+
+    result
+}
+
+fn edit_in(dir: &Path, text: &str) -> Result<String, Error> {
+    let path = dir.join("entry.txt");
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&path)
+        .and_then(|mut f| f.write_all(text.as_bytes()))
+        .with_context(|| {
+            format!("Unable to write to \"{}\"", path.display())
+        })?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+
+    // go through the shell so things like EDITOR="code --wait" work
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Unable to start \"{}\"", editor))?;
+
+    if !status.success() {
+        anyhow::bail!("\"{}\" exited unsuccessfully ({})", editor, status);
+    }
+
+    fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read \"{}\"", path.display()))
+}
+
+/// Find a directory on a memory-backed filesystem, checking
+/// `$LASTPASS_TMPDIR`, `$XDG_RUNTIME_DIR` and `/dev/shm` in that order.
+fn secure_tmpdir() -> Result<PathBuf, Error> {
+    let mounts = fs::read_to_string("/proc/self/mounts").context(
+        "Unable to read /proc/self/mounts to find a memory-backed directory",
+    )?;
+
+    let candidates = std::env::var_os("LASTPASS_TMPDIR")
+        .into_iter()
+        .chain(std::env::var_os("XDG_RUNTIME_DIR"))
+        .map(PathBuf::from)
+        .chain(std::iter::once(PathBuf::from("/dev/shm")));
+
+    for candidate in candidates {
+        let candidate = match candidate.canonicalize() {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+
+        match filesystem_type(&mounts, &candidate) {
+            Some(fs) if MEMORY_FILESYSTEMS.contains(&fs.as_str()) => {
+                return Ok(candidate)
+            },
+            other => log::debug!(
+                "Skipping \"{}\" because it is on {:?}",
+                candidate.display(),
+                other
+            ),
+        }
+    }
+
+    anyhow::bail!(
+        "Refusing to write plaintext to disk because no memory-backed \
+         (tmpfs) directory was found. Set $LASTPASS_TMPDIR to one."
+    )
+}
+
+/// Use the contents of `/proc/self/mounts` to find out which filesystem a
+/// path lives on.
+fn filesystem_type(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let _device = words.next()?;
+            let mount_point = unescape(words.next()?);
+            let fs_type = words.next()?;
+
+            Some((PathBuf::from(mount_point), fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        // the most specific mount point wins, and later mounts shadow
+        // earlier ones
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, fs_type)| fs_type.to_string())
+}
+
+/// Mount points escape spaces and friends as octal (e.g. `\040`).
+fn unescape(raw: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = raw;
+
+    while let Some(backslash) = rest.find('\\') {
+        unescaped.push_str(&rest[..backslash]);
+        let escape = rest.get(backslash + 1..backslash + 4);
+
+        match escape.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[backslash + 4..];
+            },
+            None => {
+                unescaped.push('\\');
+                rest = &rest[backslash + 1..];
+            },
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn create_private_dir(dir: &Path) -> Result<(), Error> {
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder
+        .create(dir)
+        .with_context(|| format!("Unable to create \"{}\"", dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+tmpfs /dev/shm tmpfs rw,nosuid,nodev 0 0
+tmpfs /run/user/1000 tmpfs rw,nosuid,nodev,relatime 0 0
+/dev/sdb1 /run/user/1000/disk ext4 rw 0 0
+/dev/sdc1 /mnt/my\\040drive vfat rw 0 0
+";
+
+    #[test]
+    fn find_the_filesystem_for_a_path() {
+        let inputs = vec![
+            ("/dev/shm", Some("tmpfs")),
+            ("/dev/shm/lastpass", Some("tmpfs")),
+            ("/run/user/1000", Some("tmpfs")),
+            ("/run/user/1000/disk/secrets", Some("ext4")),
+            ("/tmp", Some("ext4")),
+            ("/mnt/my drive/file", Some("vfat")),
+        ];
+
+        for (path, should_be) in inputs {
+            let got = filesystem_type(MOUNTS, Path::new(path));
+
+            assert_eq!(got.as_deref(), should_be, "{}", path);
+        }
+    }
+
+    #[test]
+    fn unescape_mount_points() {
+        assert_eq!(unescape(r"/mnt/my\040drive"), "/mnt/my drive");
+        assert_eq!(unescape(r"/plain"), "/plain");
+        assert_eq!(unescape(r"/odd\x"), r"/odd\x");
+    }
+}
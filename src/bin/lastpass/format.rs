@@ -0,0 +1,221 @@
+//! `lpass`-style format strings (e.g. `"%aN [id: %ai]"`) for printing
+//! accounts.
+
+use anyhow::Error;
+use lastpass::Account;
+use std::{fmt::Write, str::FromStr};
+
+/// A parsed format string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Format {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Placeholder {
+    /// `%ai`
+    Id,
+    /// `%an`
+    Name,
+    /// `%aN`
+    FullName,
+    /// `%au`
+    Username,
+    /// `%ap`
+    Password,
+    /// `%am`
+    LastModified,
+    /// `%aU`
+    LastTouch,
+    /// `%ag`
+    Group,
+    /// `%al`
+    Url,
+    /// `%ac`
+    Attachments,
+}
+
+impl Placeholder {
+    fn from_char(c: char) -> Option<Self> {
+        let placeholder = match c {
+            'i' => Placeholder::Id,
+            'n' => Placeholder::Name,
+            'N' => Placeholder::FullName,
+            'u' => Placeholder::Username,
+            'p' => Placeholder::Password,
+            'm' => Placeholder::LastModified,
+            'U' => Placeholder::LastTouch,
+            'g' => Placeholder::Group,
+            'l' => Placeholder::Url,
+            'c' => Placeholder::Attachments,
+            _ => return None,
+        };
+
+        Some(placeholder)
+    }
+
+    fn write(self, account: &Account, buffer: &mut String) {
+        let _ = match self {
+            Placeholder::Id => write!(buffer, "{}", &*account.id),
+            Placeholder::Name => write!(buffer, "{}", account.name),
+            Placeholder::FullName => write!(buffer, "{}", account.path()),
+            Placeholder::Username => write!(buffer, "{}", account.username),
+            Placeholder::Password => write!(buffer, "{}", account.password),
+            Placeholder::LastModified => {
+                write!(buffer, "{}", timestamp(&account.last_modified))
+            },
+            Placeholder::LastTouch => {
+                write!(buffer, "{}", timestamp(&account.last_touch))
+            },
+            Placeholder::Group => write!(buffer, "{}", account.folder()),
+            Placeholder::Url => write!(buffer, "{}", account.url),
+            Placeholder::Attachments => {
+                write!(buffer, "{}", account.attachments.len())
+            },
+        };
+    }
+}
+
+impl Format {
+    /// Render the format string for a particular account.
+    pub fn render(&self, account: &Account) -> String {
+        let mut buffer = String::new();
+
+        for token in &self.tokens {
+            match token {
+                Token::Literal(text) => buffer.push_str(text),
+                Token::Placeholder(p) => p.write(account, &mut buffer),
+            }
+        }
+
+        buffer
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                literal.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('%') => literal.push('%'),
+                Some('a') => {
+                    let placeholder = chars
+                        .next()
+                        .and_then(Placeholder::from_char)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "Unknown account placeholder in \"{}\"",
+                                s
+                            )
+                        })?;
+
+                    if !literal.is_empty() {
+                        tokens
+                            .push(Token::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(Token::Placeholder(placeholder));
+                },
+                _ => anyhow::bail!("Unknown placeholder in \"{}\"", s),
+            }
+        }
+
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+
+        Ok(Format { tokens })
+    }
+}
+
+/// Format a LastPass timestamp (seconds since the Unix epoch, in UTC) as
+/// `YYYY-MM-DD HH:MM`, or `"never"` if it isn't set.
+pub fn timestamp(raw: &str) -> String {
+    let seconds: u64 = match raw.parse() {
+        Ok(0) | Err(_) => return String::from("never"),
+        Ok(seconds) => seconds,
+    };
+
+    let days = seconds / 86_400;
+    let seconds_today = seconds % 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_today / 3600,
+        seconds_today % 3600 / 60
+    )
+}
+
+/// Convert a number of days since the Unix epoch into a (year, month, day)
+/// date, see <http://howardhinnant.github.io/date_algorithms.html>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::tests::dummy_vault;
+
+    #[test]
+    fn render_placeholders() {
+        let vault = dummy_vault();
+        let account = &vault.accounts[0];
+        let format: Format = "%aN (%au) [id: %ai] 100%% %ac".parse().unwrap();
+
+        let got = format.render(account);
+
+        assert_eq!(
+            got,
+            "Some Folder/Nested/Example password without folder (username) \
+             [id: 5496230974130180673] 100% 0"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected() {
+        assert!("%ax".parse::<Format>().is_err());
+        assert!("%q".parse::<Format>().is_err());
+        assert!("trailing %".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn format_timestamps() {
+        assert_eq!(timestamp("0"), "never");
+        assert_eq!(timestamp(""), "never");
+        assert_eq!(timestamp("1586717585"), "2020-04-12 18:53");
+        assert_eq!(timestamp("951782400"), "2000-02-29 00:00");
+    }
+}
@@ -0,0 +1,105 @@
+//! A command-line interface to LastPass, in the spirit of `lpass`.
+
+#[cfg(unix)]
+mod agent;
+#[cfg(not(unix))]
+#[path = "no_agent.rs"]
+mod agent;
+mod commands;
+mod editor;
+mod format;
+mod store;
+mod template;
+
+use anyhow::Error;
+use commands::{
+    Add, Agent, Duplicate, Edit, Exec, Generate, GitCredential, Login, Logout,
+    Ls, Mv, Render, Rm, Show, Status, Sync,
+};
+use structopt::{clap::AppSettings, StructOpt};
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let args = Args::from_args();
+    log::debug!("Starting application with {:?}", args);
+
+    if let Err(e) = args.command.run().await {
+        eprintln!("Error: {}", e);
+
+        for cause in e.chain().skip(1) {
+            eprintln!("\tCaused by: {}", cause);
+        }
+
+        std::process::exit(1);
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "lastpass", about = "An unofficial LastPass client")]
+struct Args {
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Log in to LastPass.
+    Login(Login),
+    /// Log out, removing any cached data.
+    Logout(Logout),
+    /// Check whether you are logged in.
+    Status(Status),
+    /// Download the latest copy of the vault.
+    Sync(Sync),
+    /// List the accounts in your vault.
+    Ls(Ls),
+    /// Show an account's details.
+    Show(Show),
+    /// Add a new account or secure note using your $EDITOR.
+    Add(Add),
+    /// Edit an existing account using your $EDITOR.
+    Edit(Edit),
+    /// Delete an account.
+    Rm(Rm),
+    /// Move an account to another folder.
+    Mv(Mv),
+    /// Make a copy of an account.
+    Duplicate(Duplicate),
+    /// Generate a new password for an account, creating the account if it
+    /// doesn't exist.
+    Generate(Generate),
+    /// Run a program with secrets from the vault in its environment.
+    Exec(Exec),
+    /// Fill in a template (e.g. a config file) with secrets from the vault.
+    Render(Render),
+    /// Act as a git credential helper (see gitcredentials(7)).
+    GitCredential(GitCredential),
+    /// Run the agent which remembers your decryption key (started
+    /// automatically).
+    #[structopt(setting = AppSettings::Hidden)]
+    Agent(Agent),
+}
+
+impl Command {
+    async fn run(self) -> Result<(), Error> {
+        match self {
+            Command::Login(login) => login.run().await,
+            Command::Logout(logout) => logout.run().await,
+            Command::Status(status) => status.run(),
+            Command::Sync(sync) => sync.run().await,
+            Command::Ls(ls) => ls.run().await,
+            Command::Show(show) => show.run().await,
+            Command::Add(add) => add.run().await,
+            Command::Edit(edit) => edit.run().await,
+            Command::Rm(rm) => rm.run().await,
+            Command::Mv(mv) => mv.run().await,
+            Command::Duplicate(duplicate) => duplicate.run().await,
+            Command::Generate(generate) => generate.run().await,
+            Command::Exec(exec) => exec.run().await,
+            Command::Render(render) => render.run().await,
+            Command::GitCredential(helper) => helper.run().await,
+            Command::Agent(agent) => agent.run(),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Stand-ins for [the agent](agent.rs) on platforms without Unix sockets.
+//!
+//! There is never an agent running, so the master password is asked for
+//! every time the vault is unlocked.
+
+use crate::store::{SavedSession, Store};
+use anyhow::Error;
+use lastpass::DecryptionKey;
+
+pub fn start(
+    _store: &Store,
+    _session: &SavedSession,
+    _decryption_key: &DecryptionKey,
+) -> Result<(), Error> {
+    Ok(())
+}
+
+pub fn decryption_key(
+    _store: &Store,
+    _session: &SavedSession,
+) -> Option<DecryptionKey> {
+    None
+}
+
+pub fn lock(_store: &Store) -> Result<(), Error> { Ok(()) }
+
+pub fn run(_store: &Store) -> Result<(), Error> {
+    anyhow::bail!("The agent is only supported on Unix")
+}
@@ -0,0 +1,315 @@
+//! Everything we remember between invocations.
+//!
+//! The store lives in `$LASTPASS_HOME`, falling back to
+//! `$XDG_DATA_HOME/lastpass` or `~/.local/share/lastpass`. The vault is
+//! cached exactly as it was downloaded, so its contents are still encrypted
+//! with the user's master key.
+
+use anyhow::{Context, Error};
+use lastpass::{PrivateKey, Session};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+const SESSION_FILE: &str = "session.json";
+const VAULT_FILE: &str = "vault.bin";
+const VAULT_VERSION_FILE: &str = "vault_version";
+#[cfg(unix)]
+const AGENT_SOCKET_FILE: &str = "agent.sock";
+
+#[derive(Debug, Clone)]
+pub struct Store {
+    dir: PathBuf,
+}
+
+impl Store {
+    /// Open the store in its default location, creating it if necessary.
+    pub fn open() -> Result<Self, Error> {
+        let dir = default_dir().context(
+            "Unable to find a home for our data, try setting $LASTPASS_HOME",
+        )?;
+
+        Store::at(dir)
+    }
+
+    pub fn at<P: Into<PathBuf>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.into();
+        create_private_dir(&dir).with_context(|| {
+            format!("Unable to create the \"{}\" directory", dir.display())
+        })?;
+
+        Ok(Store { dir })
+    }
+
+    /// The currently logged in user's session, if there is one.
+    pub fn session(&self) -> Result<Option<SavedSession>, Error> {
+        match self.read(SESSION_FILE)? {
+            Some(raw) => {
+                let session = serde_json::from_slice(&raw)
+                    .context("Unable to parse the saved session")?;
+                Ok(Some(session))
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_session(&self, session: &SavedSession) -> Result<(), Error> {
+        let raw = serde_json::to_vec_pretty(session)?;
+        self.write(SESSION_FILE, &raw)
+    }
+
+    /// The cached vault and its version number.
+    pub fn vault(&self) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        let version = match self.read(VAULT_VERSION_FILE)? {
+            Some(raw) => String::from_utf8_lossy(&raw)
+                .trim()
+                .parse()
+                .context("Unable to parse the cached vault version")?,
+            None => return Ok(None),
+        };
+
+        Ok(self.read(VAULT_FILE)?.map(|blob| (version, blob)))
+    }
+
+    pub fn save_vault(&self, version: u64, blob: &[u8]) -> Result<(), Error> {
+        self.write(VAULT_FILE, blob)?;
+        self.write(VAULT_VERSION_FILE, version.to_string().as_bytes())
+    }
+
+    /// Where the [`crate::agent`] listens for connections.
+    #[cfg(unix)]
+    pub fn agent_socket(&self) -> PathBuf { self.dir.join(AGENT_SOCKET_FILE) }
+
+    /// Forget everything we know about the current user.
+    pub fn clear(&self) -> Result<(), Error> {
+        for name in &[SESSION_FILE, VAULT_FILE, VAULT_VERSION_FILE] {
+            match fs::remove_file(self.dir.join(name)) {
+                Ok(_) => {},
+                Err(e) if e.kind() == ErrorKind::NotFound => {},
+                Err(e) => {
+                    return Err(Error::from(e)
+                        .context(format!("Unable to remove \"{}\"", name)))
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.dir.join(name);
+
+        match fs::read(&path) {
+            Ok(raw) => Ok(Some(raw)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::from(e)
+                .context(format!("Unable to read \"{}\"", path.display()))),
+        }
+    }
+
+    fn write(&self, name: &str, contents: &[u8]) -> Result<(), Error> {
+        write_private(&self.dir.join(name), contents)
+    }
+}
+
+/// The bits of a [`Session`] we need to keep using it later on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub username: String,
+    pub host: String,
+    pub uid: String,
+    pub token: String,
+    pub session_id: String,
+    pub iterations: usize,
+    /// The hex-encoded private key.
+    pub private_key: String,
+}
+
+impl SavedSession {
+    pub fn new(username: &str, host: &str, session: &Session) -> Self {
+        SavedSession {
+            username: username.to_string(),
+            host: host.to_string(),
+            uid: session.uid.clone(),
+            token: session.token.clone(),
+            session_id: session.session_id.clone(),
+            iterations: session.iterations,
+            private_key: hex::encode(session.private_key.as_bytes()),
+        }
+    }
+
+    pub fn private_key(&self) -> Result<PrivateKey, Error> {
+        self.private_key
+            .parse()
+            .context("The saved private key is corrupted")
+    }
+}
+
+fn default_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("LASTPASS_HOME") {
+        return Some(PathBuf::from(dir));
+    }
+
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+
+    Some(data_dir.join("lastpass"))
+}
+
+/// Atomically replace a file, making sure only the current user can read
+/// it.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let name = path
+        .file_name()
+        .with_context(|| format!("\"{}\" isn't a file", path.display()))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut f = options
+        .open(&temp)
+        .with_context(|| format!("Unable to create \"{}\"", temp.display()))?;
+
+    let written = f
+        .write_all(contents)
+        .and_then(|_| f.sync_all())
+        .and_then(|_| restrict_permissions(&temp))
+        .and_then(|_| fs::rename(&temp, path));
+
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(Error::from(e)
+            .context(format!("Unable to save \"{}\"", path.display())));
+    }
+
+    Ok(())
+}
+
+/// Make sure a file is only readable by its owner, in case the umask got in
+/// the way when it was created.
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> std::io::Result<()> { Ok(()) }
+
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> Store {
+        let dir = std::env::temp_dir().join(format!(
+            "lastpass-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+
+        Store::at(dir).unwrap()
+    }
+
+    fn session() -> SavedSession {
+        SavedSession {
+            username: String::from("joe@example.com"),
+            host: String::from("lastpass.com"),
+            uid: String::from("1234"),
+            token: String::from("TOKEN"),
+            session_id: String::from("SESSION"),
+            iterations: 100_100,
+            private_key: String::from("deadbeef"),
+        }
+    }
+
+    #[test]
+    fn save_and_clear_everything() {
+        let store = temp_store("save_and_clear");
+        assert_eq!(store.session().unwrap(), None);
+        assert_eq!(store.vault().unwrap(), None);
+
+        store.save_session(&session()).unwrap();
+        store.save_vault(42, b"LPAV").unwrap();
+
+        assert_eq!(store.session().unwrap(), Some(session()));
+        assert_eq!(store.vault().unwrap(), Some((42, b"LPAV".to_vec())));
+
+        store.clear().unwrap();
+        assert_eq!(store.session().unwrap(), None);
+        assert_eq!(store.vault().unwrap(), None);
+
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn only_the_user_can_read_the_store() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let store = temp_store("permissions");
+        store.save_session(&session()).unwrap();
+
+        let mode = |path: &Path| {
+            fs::metadata(path).unwrap().permissions().mode() & 0o777
+        };
+        assert_eq!(mode(&store.dir), 0o700);
+        assert_eq!(mode(&store.dir.join(SESSION_FILE)), 0o600);
+
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn private_files_replace_readable_ones() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir()
+            .join(format!("lastpass-write-private-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("config.toml");
+        fs::write(&output, "old contents").unwrap();
+        fs::set_permissions(&output, fs::Permissions::from_mode(0o644))
+            .unwrap();
+
+        write_private(&output, b"password = \"hunter2\"").unwrap();
+
+        let metadata = fs::metadata(&output).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "password = \"hunter2\""
+        );
+        // no temporary files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{Account, Attachment, Id, Vault};
+use serde_derive::Serialize;
+
+/// The differences between two snapshots of a [`Vault`], as returned by
+/// [`Vault::diff()`].
+///
+/// Only [`Id`]s and the names of changed fields are recorded, never their
+/// values, so it's safe to keep these around (e.g. in an audit log).
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct VaultDiff {
+    /// Accounts which only exist in the newer vault.
+    pub added: Vec<Id>,
+    /// Accounts which only exist in the older vault.
+    pub removed: Vec<Id>,
+    /// Accounts which exist in both vaults, but have changed.
+    pub modified: Vec<ModifiedAccount>,
+}
+
+impl VaultDiff {
+    /// Are the two vaults the same?
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+/// An [`Account`] which was changed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct ModifiedAccount {
+    pub id: Id,
+    pub changes: Vec<Change>,
+}
+
+/// A single change to an [`Account`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "kind", content = "attachment", rename_all = "snake_case")]
+pub enum Change {
+    Renamed,
+    /// The account was moved to another folder.
+    MovedGroup,
+    UrlChanged,
+    UsernameChanged,
+    PasswordChanged,
+    NoteChanged,
+    NoteTypeChanged,
+    FavouriteChanged,
+    PasswordProtectionChanged,
+    AttachmentAdded(Id),
+    AttachmentRemoved(Id),
+    /// A new version of the attachment was uploaded, or it was renamed.
+    AttachmentModified(Id),
+}
+
+pub(crate) fn diff(before: &Vault, after: &Vault) -> VaultDiff {
+    let mut diff = VaultDiff::default();
+
+    for new in &after.accounts {
+        match before.get_account_by_id(&new.id) {
+            Some(old) => {
+                let changes = account_changes(old, new);
+
+                if !changes.is_empty() {
+                    diff.modified.push(ModifiedAccount {
+                        id: new.id.clone(),
+                        changes,
+                    });
+                }
+            },
+            None => diff.added.push(new.id.clone()),
+        }
+    }
+
+    diff.removed = before
+        .accounts
+        .iter()
+        .filter(|old| after.get_account_by_id(&old.id).is_none())
+        .map(|old| old.id.clone())
+        .collect();
+
+    diff
+}
+
+fn account_changes(old: &Account, new: &Account) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let fields = [
+        (old.name != new.name, Change::Renamed),
+        (old.group != new.group, Change::MovedGroup),
+        (old.url != new.url, Change::UrlChanged),
+        (old.username != new.username, Change::UsernameChanged),
+        (old.password != new.password, Change::PasswordChanged),
+        (old.note != new.note, Change::NoteChanged),
+        (old.note_type != new.note_type, Change::NoteTypeChanged),
+        (old.favourite != new.favourite, Change::FavouriteChanged),
+        (
+            old.password_protected != new.password_protected,
+            Change::PasswordProtectionChanged,
+        ),
+    ];
+    changes.extend(
+        fields
+            .iter()
+            .filter(|(changed, _)| *changed)
+            .map(|(_, change)| change.clone()),
+    );
+
+    for attachment in &new.attachments {
+        match find_attachment(&old.attachments, &attachment.id) {
+            Some(previous) if previous != attachment => {
+                changes.push(Change::AttachmentModified(attachment.id.clone()))
+            },
+            Some(_) => {},
+            None => {
+                changes.push(Change::AttachmentAdded(attachment.id.clone()))
+            },
+        }
+    }
+
+    for attachment in &old.attachments {
+        if find_attachment(&new.attachments, &attachment.id).is_none() {
+            changes.push(Change::AttachmentRemoved(attachment.id.clone()));
+        }
+    }
+
+    changes
+}
+
+fn find_attachment<'a>(
+    attachments: &'a [Attachment],
+    id: &Id,
+) -> Option<&'a Attachment> {
+    attachments.iter().find(|attachment| attachment.id == *id)
+}
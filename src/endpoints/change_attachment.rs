@@ -0,0 +1,189 @@
+//! Endpoints for changing an existing [`Attachment`].
+//!
+//! The `deleteattach.php` and `renameattach.php` request and response formats
+//! were written by hand and haven't been confirmed against the real server,
+//! so these are only available with the `unstable-attachments` feature.
+
+use super::{save_account::Document, Redacted, RetryPolicy, SendError};
+use crate::{Attachment, DecryptionKey, Id};
+use reqwest::{Client, Error as ReqwestError};
+use serde_derive::Serialize;
+
+/// Permanently delete an [`Attachment`].
+///
+/// Use [`crate::Account::remove_attachment()`] afterwards to keep your local
+/// copy of the [`crate::Account`] in sync.
+pub async fn delete_attachment(
+    client: &Client,
+    hostname: &str,
+    token: &str,
+    attachment_id: &Id,
+) -> Result<(), ChangeAttachmentError> {
+    let data = Data {
+        token: Redacted(token),
+        attachment_id,
+        filename: None,
+    };
+
+    send_change(client, hostname, "deleteattach.php", &data).await
+}
+
+/// Change an [`Attachment`]'s filename.
+///
+/// The new filename is encrypted using the parent account's attachment key
+/// (see [`crate::Account::attachment_key()`]) and, on success,
+/// [`Attachment::encrypted_filename`] is updated to match.
+pub async fn rename_attachment(
+    client: &Client,
+    hostname: &str,
+    token: &str,
+    attachment: &mut Attachment,
+    attachment_key: &DecryptionKey,
+    new_filename: &str,
+) -> Result<(), ChangeAttachmentError> {
+    let encrypted_filename = attachment_key.encrypt_base64(new_filename);
+    let data = Data {
+        token: Redacted(token),
+        attachment_id: &attachment.id,
+        filename: Some(Redacted(&encrypted_filename)),
+    };
+
+    send_change(client, hostname, "renameattach.php", &data).await?;
+
+    attachment.encrypted_filename = encrypted_filename;
+
+    Ok(())
+}
+
+async fn send_change(
+    client: &Client,
+    hostname: &str,
+    path: &str,
+    data: &Data<'_>,
+) -> Result<(), ChangeAttachmentError> {
+    let body = super::send(client, hostname, path, data, &RetryPolicy::never())
+        .await?;
+
+    let doc: Document = serde_xml_rs::from_reader(&body[..])?;
+
+    interpret_response(doc)
+}
+
+fn interpret_response(doc: Document) -> Result<(), ChangeAttachmentError> {
+    match doc {
+        Document {
+            error: Some(error), ..
+        } => Err(ChangeAttachmentError::RejectedByServer {
+            message: error.message,
+        }),
+        Document {
+            result: Some(_), ..
+        } => Ok(()),
+        _ => Err(ChangeAttachmentError::RejectedByServer {
+            message: String::new(),
+        }),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Data<'a> {
+    token: Redacted<&'a str>,
+    #[serde(rename = "id")]
+    attachment_id: &'a Id,
+    /// The new (encrypted) filename, when renaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filename: Option<Redacted<&'a str>>,
+}
+
+/// Possible errors that may be returned by [`delete_attachment()`] and
+/// [`rename_attachment()`].
+#[derive(Debug, thiserror::Error)]
+pub enum ChangeAttachmentError {
+    /// The HTTP client encountered an error.
+    #[error("Unable to send the request")]
+    HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] super::RateLimited),
+    /// Unable to parse the response.
+    #[error("Unable to parse the response")]
+    ResponseParse(#[from] serde_xml_rs::Error),
+    /// The server refused to change the attachment.
+    #[error("The server rejected the change: {}", message)]
+    RejectedByServer { message: String },
+}
+
+impl From<SendError> for ChangeAttachmentError {
+    fn from(e: SendError) -> ChangeAttachmentError {
+        match e {
+            SendError::HttpClient(e) => ChangeAttachmentError::HttpClient(e),
+            SendError::RateLimited(e) => ChangeAttachmentError::RateLimited(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection_message(src: &str) -> String {
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        match interpret_response(got) {
+            Err(ChangeAttachmentError::RejectedByServer { message }) => message,
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_delete_response() {
+        let src = include_str!("delete_attachment_okay.xml");
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        assert!(interpret_response(got).is_ok());
+    }
+
+    #[test]
+    fn parse_delete_error() {
+        let src = include_str!("delete_attachment_error.xml");
+
+        assert_eq!(rejection_message(src), "Unable to delete the attachment");
+    }
+
+    #[test]
+    fn parse_rename_response() {
+        let src = include_str!("rename_attachment_okay.xml");
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        assert!(interpret_response(got).is_ok());
+    }
+
+    #[test]
+    fn parse_rename_error() {
+        let src = include_str!("rename_attachment_error.xml");
+
+        assert_eq!(rejection_message(src), "Unable to rename the attachment");
+    }
+
+    #[test]
+    fn only_renames_send_a_filename() {
+        let id = Id::from("1234");
+        let delete = Data {
+            token: Redacted("token"),
+            attachment_id: &id,
+            filename: None,
+        };
+        let rename = Data {
+            filename: Some(Redacted("encrypted")),
+            ..delete
+        };
+
+        let delete = serde_json::to_value(&delete).unwrap();
+        let rename = serde_json::to_value(&rename).unwrap();
+
+        assert!(delete.get("filename").is_none());
+        assert_eq!(rename["filename"], "encrypted");
+    }
+}
@@ -0,0 +1,128 @@
+use super::{save_account::Document, Redacted, RetryPolicy, SendError};
+use crate::{Id, Share};
+use reqwest::{Client, Error as ReqwestError};
+use serde_derive::Serialize;
+
+/// Permanently delete an [`crate::Account`].
+///
+/// Accounts in a shared folder (see [`crate::Account::share`]) need to say
+/// which [`Share`] they belong to.
+pub async fn delete_account(
+    client: &Client,
+    hostname: &str,
+    token: &str,
+    account_id: &Id,
+    share: Option<&Share>,
+) -> Result<(), DeleteAccountError> {
+    let data = Data {
+        extjs: 1,
+        token: Redacted(token),
+        delete: 1,
+        account_id,
+        share_id: share.map(|share| &share.id),
+    };
+
+    let body = super::send(
+        client,
+        hostname,
+        "show_website.php",
+        &data,
+        &RetryPolicy::never(),
+    )
+    .await?;
+
+    let doc: Document = serde_xml_rs::from_reader(&body[..])?;
+
+    match doc {
+        Document {
+            error: Some(error), ..
+        } => Err(DeleteAccountError::RejectedByServer {
+            message: error.message,
+        }),
+        Document {
+            result: Some(_), ..
+        } => Ok(()),
+        _ => Err(DeleteAccountError::RejectedByServer {
+            message: String::new(),
+        }),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Data<'a> {
+    extjs: usize,
+    token: Redacted<&'a str>,
+    delete: usize,
+    #[serde(rename = "aid")]
+    account_id: &'a Id,
+    #[serde(
+        rename = "sharedfolderid",
+        skip_serializing_if = "Option::is_none"
+    )]
+    share_id: Option<&'a Id>,
+}
+
+/// Possible errors that may be returned by [`delete_account()`].
+#[derive(Debug, thiserror::Error)]
+pub enum DeleteAccountError {
+    /// The HTTP client encountered an error.
+    #[error("Unable to send the request")]
+    HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] super::RateLimited),
+    /// Unable to parse the response.
+    #[error("Unable to parse the response")]
+    ResponseParse(#[from] serde_xml_rs::Error),
+    /// The server refused to delete the account.
+    #[error("The server rejected the change: {}", message)]
+    RejectedByServer { message: String },
+}
+
+impl From<SendError> for DeleteAccountError {
+    fn from(e: SendError) -> DeleteAccountError {
+        match e {
+            SendError::HttpClient(e) => DeleteAccountError::HttpClient(e),
+            SendError::RateLimited(e) => DeleteAccountError::RateLimited(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_delete_response() {
+        let src = include_str!("delete_account_okay.xml");
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        assert_eq!(got.result.unwrap().action, "delete");
+        assert!(got.error.is_none());
+    }
+
+    #[test]
+    fn shared_accounts_say_which_folder_they_are_in() {
+        let share = Share::new(
+            Id::from("42"),
+            String::from("Shared-Team"),
+            crate::DecryptionKey::generate(),
+            false,
+        );
+        let account_id = Id::from("1234");
+        let data = |share| Data {
+            extjs: 1,
+            token: Redacted("token"),
+            delete: 1,
+            account_id: &account_id,
+            share_id: share,
+        };
+
+        let shared = serde_json::to_value(data(Some(&share.id))).unwrap();
+        let personal = serde_json::to_value(data(None)).unwrap();
+
+        assert_eq!(shared["sharedfolderid"], "42");
+        assert!(personal.get("sharedfolderid").is_none());
+    }
+}
@@ -0,0 +1,406 @@
+//! Diagnostics for endpoint calls.
+//!
+//! When the `tracing` feature is enabled each call to an endpoint gets its own
+//! span. Only metadata (the path, status code, byte counts and timings) is
+//! ever recorded, never the request payload or response body. The debug logs
+//! are the same, except they also list the names of the payload's fields.
+
+use reqwest::StatusCode;
+use serde::{
+    ser::{self, Impossible, SerializeStruct},
+    Serialize, Serializer,
+};
+use std::{
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    time::Instant,
+};
+
+/// Bookkeeping for a single call to an endpoint, including any retries.
+pub(crate) struct Call<'a> {
+    path: &'a str,
+    started: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'a> Call<'a> {
+    pub(crate) fn start(path: &'a str) -> Self {
+        Call {
+            path,
+            started: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "endpoint",
+                path,
+                attempts = tracing::field::Empty,
+                status = tracing::field::Empty,
+                request_bytes = tracing::field::Empty,
+                response_bytes = tracing::field::Empty,
+                duration_ms = tracing::field::Empty,
+            ),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &'a str { self.path }
+
+    /// Run a future inside this call's span.
+    #[cfg(feature = "tracing")]
+    pub(crate) async fn instrument<F: Future>(&self, future: F) -> F::Output {
+        use tracing::Instrument;
+
+        future.instrument(self.span.clone()).await
+    }
+
+    /// Run a future inside this call's span.
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) async fn instrument<F: Future>(&self, future: F) -> F::Output {
+        future.await
+    }
+
+    pub(crate) fn record_request(&self, attempt: u32, bytes: Option<usize>) {
+        log::debug!(
+            "Sending a request to {} (attempt {}, {:?} bytes)",
+            self.path,
+            attempt + 1,
+            bytes
+        );
+
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("attempts", attempt + 1);
+            if let Some(bytes) = bytes {
+                self.span.record("request_bytes", bytes);
+            }
+        }
+    }
+
+    pub(crate) fn record_status(&self, status: StatusCode) {
+        log::debug!("{} responded with {}", self.path, status);
+
+        #[cfg(feature = "tracing")]
+        self.span.record("status", status.as_u16());
+    }
+
+    pub(crate) fn finish(self, response_bytes: Option<usize>) {
+        let duration = self.started.elapsed();
+        log::debug!(
+            "Call to {} finished in {:?} ({:?} bytes received)",
+            self.path,
+            duration,
+            response_bytes
+        );
+
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("duration_ms", duration.as_millis() as u64);
+            if let Some(bytes) = response_bytes {
+                self.span.record("response_bytes", bytes);
+            }
+        }
+    }
+}
+
+/// A wrapper for sensitive values (tokens, password hashes, etc.) which are
+/// sent to the server as-is, but never appear in [`Debug`] output.
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) struct Redacted<T>(pub T);
+
+impl<T: Serialize> Serialize for Redacted<T> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(ser)
+    }
+}
+
+impl<T> Debug for Redacted<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// The names of the fields in a request's payload, for logging.
+///
+/// Values are left out because even encrypted ones (e.g. a whole attachment)
+/// can be huge, and they shouldn't end up in the logs anyway. The
+/// [`FieldNames`] serializer never even looks at them, so nothing gets copied.
+pub(crate) fn field_names<D: Serialize>(data: &D) -> Vec<&'static str> {
+    let mut names = FieldNames::default();
+
+    match data.serialize(&mut names) {
+        Ok(()) => names.0,
+        Err(_) => Vec::new(),
+    }
+}
+
+/// A [`Serializer`] which only records the names of a struct's fields.
+///
+/// Payloads are always structs, so anything else is an error.
+#[derive(Debug, Default)]
+struct FieldNames(Vec<&'static str>);
+
+#[derive(Debug)]
+struct NotAStruct;
+
+impl fmt::Display for NotAStruct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Only structs have field names")
+    }
+}
+
+impl std::error::Error for NotAStruct {}
+
+impl ser::Error for NotAStruct {
+    fn custom<T: fmt::Display>(_msg: T) -> Self { NotAStruct }
+}
+
+impl SerializeStruct for &mut FieldNames {
+    type Error = NotAStruct;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        _value: &T,
+    ) -> Result<(), NotAStruct>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.push(key);
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), NotAStruct> { Ok(()) }
+}
+
+impl Serializer for &mut FieldNames {
+    type Error = NotAStruct;
+    type Ok = ();
+    type SerializeMap = Impossible<(), NotAStruct>;
+    type SerializeSeq = Impossible<(), NotAStruct>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), NotAStruct>;
+    type SerializeTuple = Impossible<(), NotAStruct>;
+    type SerializeTupleStruct = Impossible<(), NotAStruct>;
+    type SerializeTupleVariant = Impossible<(), NotAStruct>;
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self, NotAStruct> {
+        Ok(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<(), NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_i16(self, _: i16) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_i32(self, _: i32) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_i64(self, _: i64) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_u8(self, _: u8) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_u16(self, _: u16) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_u32(self, _: u32) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_u64(self, _: u64) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_f32(self, _: f32) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_f64(self, _: f64) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_char(self, _: char) -> Result<(), NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_str(self, _: &str) -> Result<(), NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_none(self) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_some<T>(self, _: &T) -> Result<(), NotAStruct>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotAStruct)
+    }
+
+    fn serialize_unit(self) -> Result<(), NotAStruct> { Err(NotAStruct) }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<(), NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), NotAStruct>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotAStruct)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), NotAStruct>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotAStruct)
+    }
+
+    fn serialize_seq(
+        self,
+        _: Option<usize>,
+    ) -> Result<Self::SerializeSeq, NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_tuple(
+        self,
+        _: usize,
+    ) -> Result<Self::SerializeTuple, NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_map(
+        self,
+        _: Option<usize>,
+    ) -> Result<Self::SerializeMap, NotAStruct> {
+        Err(NotAStruct)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, NotAStruct> {
+        Err(NotAStruct)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize)]
+    struct Data<'a> {
+        username: &'a str,
+        token: Redacted<&'a str>,
+    }
+
+    #[test]
+    fn redacted_values_are_still_serialized() {
+        let data = Data {
+            username: "joe",
+            token: Redacted("SECRET"),
+        };
+
+        let got = serde_json::to_string(&data).unwrap();
+
+        assert_eq!(got, r#"{"username":"joe","token":"SECRET"}"#);
+    }
+
+    #[test]
+    fn redacted_values_never_show_up_in_debug_output() {
+        let data = Data {
+            username: "joe",
+            token: Redacted("SECRET"),
+        };
+
+        let got = format!("{:?}", data);
+
+        assert!(!got.contains("SECRET"));
+        assert!(got.contains("<redacted>"));
+    }
+
+    #[test]
+    fn only_field_names_are_logged() {
+        let data = Data {
+            username: "joe",
+            token: Redacted("SECRET"),
+        };
+
+        let got = field_names(&data);
+
+        assert_eq!(got, vec!["username", "token"]);
+    }
+
+    /// A value which would panic if [`field_names()`] ever serialized it.
+    struct Untouchable;
+
+    impl Serialize for Untouchable {
+        fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            panic!("The value was serialized")
+        }
+    }
+
+    #[test]
+    fn values_are_never_serialized() {
+        #[derive(Serialize)]
+        struct Payload {
+            data: Untouchable,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            skipped: Option<u32>,
+        }
+
+        let got = field_names(&Payload {
+            data: Untouchable,
+            skipped: None,
+        });
+
+        assert_eq!(got, vec!["data"]);
+    }
+
+    #[test]
+    fn only_structs_have_field_names() {
+        assert!(field_names(&"a string").is_empty());
+        assert!(field_names(&vec![1, 2, 3]).is_empty());
+    }
+}
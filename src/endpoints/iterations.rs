@@ -1,4 +1,4 @@
-use super::EndpointError;
+use super::{EndpointError, RetryPolicy};
 use reqwest::Client;
 use serde_derive::{Deserialize, Serialize};
 
@@ -7,26 +7,17 @@ pub async fn iterations(
     client: &Client,
     hostname: &str,
     username: &str,
+    retry: &RetryPolicy,
 ) -> Result<usize, EndpointError> {
-    let url = format!("https://{}/iterations.php", hostname);
     let data = IterationsData { email: username };
 
-    log::debug!("Sending an iterations request to {}", url);
-    log::trace!("Payload: {:#?}", data);
+    let body =
+        super::send(client, hostname, "iterations.php", &data, retry).await?;
 
-    let response = client
-        .post(&url)
-        .form(&data)
-        .send()
-        .await?
-        .error_for_status()?;
-
-    log::trace!("Response Headers: {:#?}", response.headers());
-
-    let body = response.text().await?;
-    log::trace!("Response Body: {}", body);
-
-    body.trim().parse().map_err(Into::into)
+    String::from_utf8_lossy(&body)
+        .trim()
+        .parse()
+        .map_err(Into::into)
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -1,6 +1,14 @@
-use crate::keys::DecryptionKey;
+use super::{RateLimited, Redacted, RetryPolicy, SendError};
+use crate::{
+    keys::{DecryptionKey, StreamDecryptor},
+    Attachment,
+};
 use reqwest::{Client, Error as ReqwestError};
 use serde_derive::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// How many bytes we need to look at when guessing a file's MIME type.
+const MIME_SNIFF_LEN: usize = 8;
 
 pub async fn load_attachment(
     client: &Client,
@@ -8,33 +16,248 @@ pub async fn load_attachment(
     token: &str,
     storage_key: &str,
     decryption_key: &DecryptionKey,
+    retry: &RetryPolicy,
 ) -> Result<Vec<u8>, LoadAttachmentError> {
-    let data = Data { token, storage_key };
+    let data = Data {
+        token: Redacted(token),
+        storage_key,
+    };
 
-    let response =
-        super::send(client, hostname, "getattach.php", &data).await?;
+    let body =
+        super::send(client, hostname, "getattach.php", &data, retry).await?;
 
-    let ciphertext: String = response.text().await?;
+    let ciphertext = String::from_utf8_lossy(&body);
     let data = decryption_key.decrypt_base64(&ciphertext)?;
 
     // not only was the ciphertext in base64, the attachment body was too
     base64::decode(data).map_err(LoadAttachmentError::Decode)
 }
 
+/// Download an [`Attachment`], decrypting it on the fly and writing the
+/// result to `writer`.
+///
+/// Unlike [`load_attachment()`], the attachment is never held in memory all
+/// at once. Once the download is complete, the number of bytes written is
+/// checked against [`Attachment::size`] and, for well-known file formats,
+/// the contents are checked against [`Attachment::mime_type`].
+///
+/// Note that by the time a [`LoadAttachmentError::SizeMismatch`] or
+/// [`LoadAttachmentError::MimeTypeMismatch`] is detected the data will have
+/// already been written, so callers may want to discard it.
+pub async fn load_attachment_to<W>(
+    client: &Client,
+    hostname: &str,
+    token: &str,
+    attachment: &Attachment,
+    attachment_key: &DecryptionKey,
+    writer: &mut W,
+    retry: &RetryPolicy,
+) -> Result<u64, LoadAttachmentError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let data = Data {
+        token: Redacted(token),
+        storage_key: &attachment.storage_key,
+    };
+
+    let mut response =
+        super::send_streaming(client, hostname, "getattach.php", &data, retry)
+            .await?;
+
+    let mut decryptor = StreamDecryptor::new(*attachment_key);
+    let mut buffer = Vec::new();
+    let mut prefix = Vec::with_capacity(MIME_SNIFF_LEN);
+    let mut bytes_written = 0;
+
+    while let Some(chunk) = response.chunk().await? {
+        decryptor.feed(&chunk, &mut buffer)?;
+        bytes_written += write_chunk(writer, &mut buffer, &mut prefix).await?;
+    }
+
+    decryptor.finish(&mut buffer)?;
+    bytes_written += write_chunk(writer, &mut buffer, &mut prefix).await?;
+    writer.flush().await?;
+
+    if bytes_written != attachment.size {
+        return Err(LoadAttachmentError::SizeMismatch {
+            expected: attachment.size,
+            actual: bytes_written,
+        });
+    }
+
+    check_mime_type(&attachment.mime_type, &prefix)?;
+
+    Ok(bytes_written)
+}
+
+async fn write_chunk<W>(
+    writer: &mut W,
+    buffer: &mut Vec<u8>,
+    prefix: &mut Vec<u8>,
+) -> Result<u64, std::io::Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    // remember the first few bytes so we can check the MIME type later
+    let missing = MIME_SNIFF_LEN.saturating_sub(prefix.len());
+    prefix.extend(buffer.iter().take(missing));
+
+    writer.write_all(buffer).await?;
+    let bytes_written = buffer.len() as u64;
+    buffer.clear();
+
+    Ok(bytes_written)
+}
+
+/// Make sure the file's contents are consistent with the MIME type LastPass
+/// has on record.
+///
+/// LastPass often uses generic MIME types (e.g. `"other:txt"`), so we can
+/// only detect a mismatch when the expected type is specific and the file is
+/// in a format we can recognise.
+fn check_mime_type(
+    expected: &str,
+    prefix: &[u8],
+) -> Result<(), LoadAttachmentError> {
+    let detected = match sniff_mime_type(prefix) {
+        Some(detected) => detected,
+        None => return Ok(()),
+    };
+
+    let normalized = expected.trim().to_lowercase().replace(':', "/");
+    let normalized = match normalized.as_str() {
+        "image/jpg" => "image/jpeg",
+        "application/x-zip-compressed" => "application/zip",
+        "application/x-gzip" => "application/gzip",
+        other => other,
+    };
+
+    if normalized.starts_with("other/")
+        || !normalized.contains('/')
+        || normalized == "application/octet-stream"
+        || normalized == detected
+    {
+        Ok(())
+    } else {
+        Err(LoadAttachmentError::MimeTypeMismatch {
+            expected: expected.to_string(),
+            detected,
+        })
+    }
+}
+
+/// Guess a file's MIME type based on its first few bytes.
+fn sniff_mime_type(prefix: &[u8]) -> Option<&'static str> {
+    const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+
+    MAGIC_NUMBERS
+        .iter()
+        .find(|(magic, _)| prefix.starts_with(magic))
+        .map(|(_, mime_type)| *mime_type)
+}
+
 #[derive(Debug, Serialize)]
 struct Data<'a> {
-    token: &'a str,
+    token: Redacted<&'a str>,
     #[serde(rename = "getattach")]
     storage_key: &'a str,
 }
 
+/// Possible errors that may be returned by [`load_attachment()`] and
+/// [`load_attachment_to()`].
 #[derive(Debug, thiserror::Error)]
 pub enum LoadAttachmentError {
     /// The HTTP client encountered an error.
     #[error("Unable to send the request")]
     HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] RateLimited),
     #[error("Unable to decrypt the payload")]
     Decrypt(#[from] crate::DecryptionError),
     #[error("Unable to decode the decrypted attachment")]
     Decode(#[from] base64::DecodeError),
+    /// Unable to write the attachment to its destination.
+    #[error("Unable to write the attachment")]
+    Io(#[from] std::io::Error),
+    /// The attachment wasn't the size we expected.
+    #[error(
+        "Expected the attachment to be {} bytes, but received {}",
+        expected,
+        actual
+    )]
+    SizeMismatch { expected: u64, actual: u64 },
+    /// The attachment's contents don't match its MIME type.
+    #[error(
+        "Expected a \"{}\" attachment, but it looks like \"{}\"",
+        expected,
+        detected
+    )]
+    MimeTypeMismatch {
+        expected: String,
+        detected: &'static str,
+    },
+}
+
+impl From<SendError> for LoadAttachmentError {
+    fn from(e: SendError) -> LoadAttachmentError {
+        match e {
+            SendError::HttpClient(e) => LoadAttachmentError::HttpClient(e),
+            SendError::RateLimited(e) => LoadAttachmentError::RateLimited(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+
+    #[test]
+    fn sniff_some_well_known_formats() {
+        let inputs: &[(&[u8], Option<&str>)] = &[
+            (PNG, Some("image/png")),
+            (b"%PDF-1.4\n", Some("application/pdf")),
+            (b"GIF89a", Some("image/gif")),
+            (b"Hello, World!", None),
+            (b"", None),
+        ];
+
+        for &(input, should_be) in inputs {
+            assert_eq!(sniff_mime_type(input), should_be);
+        }
+    }
+
+    #[test]
+    fn generic_mime_types_are_always_accepted() {
+        assert!(check_mime_type("other:txt", PNG).is_ok());
+        assert!(check_mime_type("application/octet-stream", PNG).is_ok());
+        assert!(check_mime_type("image:png", PNG).is_ok());
+        assert!(check_mime_type("image/png", PNG).is_ok());
+        // we can't tell what type a text file is
+        assert!(check_mime_type("image/png", b"Hello, World!").is_ok());
+    }
+
+    #[test]
+    fn detect_mismatched_mime_types() {
+        let got = check_mime_type("application/pdf", PNG).unwrap_err();
+
+        match got {
+            LoadAttachmentError::MimeTypeMismatch { expected, detected } => {
+                assert_eq!(expected, "application/pdf");
+                assert_eq!(detected, "image/png");
+            },
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
 }
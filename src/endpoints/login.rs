@@ -1,10 +1,11 @@
+use super::{RateLimited, Redacted, RetryPolicy, SendError};
 use crate::{
-    keys::{LoginKey, PrivateKey},
+    keys::{self, DecryptionKey, LoginKey, PrivateKey},
     Session,
 };
 use reqwest::{Client, Error as ReqwestError};
 use serde_derive::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{future::Future, str::FromStr};
 
 /// Authenticate with the LastPass servers and get a new [`Session`].
 ///
@@ -22,25 +23,90 @@ pub async fn login(
     let data = Data {
         xml: 2,
         username,
-        hash: login_key.as_hex(),
+        hash: Redacted(login_key.as_hex()),
         iterations,
         includeprivatekeyenc: 1,
         method: "cli",
         outofbandsupported: 1,
-        trusted_id,
+        trusted_id: trusted_id.map(Redacted),
     };
-    let response = super::send(client, hostname, "login.php", &data).await?;
+    // logging in isn't idempotent, so we never retry
+    let body = super::send(
+        client,
+        hostname,
+        "login.php",
+        &data,
+        &RetryPolicy::never(),
+    )
+    .await?;
 
-    let body = response.text().await?;
-    log::trace!("Response: {}", body);
+    let doc: Document = serde_xml_rs::from_reader(&body[..])?;
 
-    let doc: Document = serde_xml_rs::from_str(&body)?;
-    log::trace!("Parsed response: {:#?}", doc);
+    interpret_response(doc.response, iterations)
+}
 
-    interpret_response(doc.response)
+/// Log in using the user's master password, calculating the [`LoginKey`] and
+/// [`DecryptionKey`] along the way.
+///
+/// The keys are derived on a background thread using
+/// [`crate::derive_keys_async()`] so this won't block the executor.
+///
+/// If the server's iteration count changed since you called
+/// [`super::iterations()`], the keys will be recalculated using the number of
+/// iterations suggested by the server and the login retried once. The
+/// iteration count which was actually used is available via
+/// [`Session::iterations`].
+pub async fn login_with_password(
+    client: &Client,
+    hostname: &str,
+    username: &str,
+    password: &str,
+    iterations: usize,
+    trusted_id: Option<&str>,
+) -> Result<(Session, DecryptionKey), LoginError> {
+    retry_on_iteration_mismatch(iterations, |iterations| async move {
+        let (login_key, decryption_key) =
+            keys::derive_keys_async(username, password, iterations).await;
+        let session = login(
+            client, hostname, username, &login_key, iterations, trusted_id,
+        )
+        .await?;
+
+        Ok((session, decryption_key))
+    })
+    .await
 }
 
-fn interpret_response(response: Root) -> Result<Session, LoginError> {
+/// Make a login `attempt`, trying once more with the server's suggested
+/// number of iterations if it says we used the wrong one.
+async fn retry_on_iteration_mismatch<F, Fut, T>(
+    iterations: usize,
+    mut attempt: F,
+) -> Result<T, LoginError>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<T, LoginError>>,
+{
+    match attempt(iterations).await {
+        Err(LoginError::IterationMismatch {
+            iterations: suggested,
+        }) if suggested != iterations => {
+            log::info!(
+                "The server expected {} iterations instead of {}, retrying",
+                suggested,
+                iterations
+            );
+
+            attempt(suggested).await
+        },
+        other => other,
+    }
+}
+
+fn interpret_response(
+    response: Root,
+    iterations: usize,
+) -> Result<Session, LoginError> {
     match response {
         Root::Error(err) => {
             log::error!("Login failed with {}: {}", err.cause, err.message);
@@ -62,6 +128,7 @@ fn interpret_response(response: Root) -> Result<Session, LoginError> {
                 token,
                 private_key,
                 session_id,
+                iterations,
             })
         },
     }
@@ -107,24 +174,34 @@ where
         .map_err(<D::Error as serde::de::Error>::custom)
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 struct ErrorMessage {
+    #[serde(default)]
     message: String,
+    #[serde(default)]
     cause: String,
     enabled_providers: Option<String>,
+    /// The number of iterations the server expected us to use.
+    iterations: Option<usize>,
+    /// The out-of-band authentication mechanism (e.g. `"lastpassauth"`).
+    #[serde(rename = "outofbandtype")]
+    out_of_band_type: Option<String>,
+    /// A human-friendly name for the out-of-band authentication mechanism.
+    #[serde(rename = "outofbandname")]
+    out_of_band_name: Option<String>,
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize)]
 struct Data<'a> {
     xml: usize,
     username: &'a str,
-    hash: &'a str,
+    hash: Redacted<&'a str>,
     iterations: usize,
     includeprivatekeyenc: usize,
     method: &'a str,
     outofbandsupported: usize,
     #[serde(rename = "uuid")]
-    trusted_id: Option<&'a str>,
+    trusted_id: Option<Redacted<&'a str>>,
 }
 
 /// Possible errors that may be returned by [`login()`].
@@ -133,6 +210,9 @@ pub enum LoginError {
     /// The HTTP client encountered an error.
     #[error("Unable to send the login request")]
     HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] RateLimited),
     /// The server indicated that you need to fetch a new two-factor token and
     /// try again.
     #[error("A new 2FA token is required")]
@@ -140,28 +220,110 @@ pub enum LoginError {
     /// Unable to parse the login response.
     #[error("Unable to parse the login response")]
     ResponseParse(#[from] serde_xml_rs::Error),
+    /// There is no account associated with this email address.
+    #[error("Unknown email address: {}", message)]
+    UnknownEmail { message: String },
+    /// The password (i.e. the [`LoginKey`]) was incorrect.
+    #[error("Incorrect password: {}", message)]
+    WrongPassword { message: String },
+    /// The [`LoginKey`] was calculated using the wrong number of iterations.
+    ///
+    /// You should recalculate your keys using the number of iterations
+    /// suggested by the server and try again.
+    #[error("The server expected {} iterations", iterations)]
+    IterationMismatch { iterations: usize },
+    /// The account has been locked (e.g. after too many failed login
+    /// attempts).
+    #[error("The account is locked: {}", message)]
+    AccountLocked { message: String },
+    /// The user needs to approve this login using an out-of-band mechanism
+    /// (e.g. a push notification to the LastPass Authenticator app).
+    #[error("Out-of-band authentication with {} is required", name)]
+    OutOfBandRequired {
+        /// The mechanism's identifier (e.g. `"lastpassauth"`).
+        kind: String,
+        /// A human-friendly name for the mechanism.
+        name: String,
+        message: String,
+    },
+    /// LastPass sent the user an email asking them to verify that this login
+    /// is coming from a new location or device.
+    #[error("This login location needs to be verified: {}", message)]
+    VerifyNewLocation { message: String },
+    /// This is a federated account (e.g. single sign-on via an identity
+    /// provider) which can't log in with a master password.
+    #[error("Federated login is required: {}", message)]
+    Federated { message: String },
     /// A catch-all error for when the server rejects a login request and we
     /// can't figure out a more specific error.
     #[error("Login was rejected by the server because {}: {}", cause, message)]
     RejectedByServer { cause: String, message: String },
 }
 
+impl From<SendError> for LoginError {
+    fn from(e: SendError) -> LoginError {
+        match e {
+            SendError::HttpClient(e) => LoginError::HttpClient(e),
+            SendError::RateLimited(e) => LoginError::RateLimited(e),
+        }
+    }
+}
+
 impl From<ErrorMessage> for LoginError {
     fn from(msg: ErrorMessage) -> LoginError {
-        if let Some(enabled_providers) = msg.enabled_providers {
-            return LoginError::TwoFactorLoginRequired(
-                TwoFactorLoginRequired { enabled_providers },
-            );
+        if let Some(iterations) = msg.iterations {
+            // the server may mention the iteration count alongside other
+            // errors (e.g. a wrong password), so it's only a mismatch when
+            // that's all it tells us or it says so explicitly
+            if msg.cause.is_empty()
+                || mentions_iterations(&msg.cause)
+                || mentions_iterations(&msg.message)
+            {
+                return LoginError::IterationMismatch { iterations };
+            }
         }
 
-        // we couldn't figure out a better error message
-        LoginError::RejectedByServer {
-            cause: msg.cause,
-            message: msg.message,
+        let ErrorMessage {
+            message,
+            cause,
+            enabled_providers,
+            out_of_band_type,
+            out_of_band_name,
+            ..
+        } = msg;
+
+        match cause.as_str() {
+            "unknownemail" => LoginError::UnknownEmail { message },
+            "unknownpassword" => LoginError::WrongPassword { message },
+            "accountlocked" => LoginError::AccountLocked { message },
+            "verifydevice" => LoginError::VerifyNewLocation { message },
+            "federated" => LoginError::Federated { message },
+            "outofbandrequired" => {
+                let kind = out_of_band_type.unwrap_or_default();
+                let name = out_of_band_name.unwrap_or_else(|| kind.clone());
+                LoginError::OutOfBandRequired {
+                    kind,
+                    name,
+                    message,
+                }
+            },
+            _ => match enabled_providers {
+                Some(enabled_providers) => {
+                    LoginError::TwoFactorLoginRequired(TwoFactorLoginRequired {
+                        enabled_providers,
+                    })
+                },
+                // we couldn't figure out a better error message
+                None => LoginError::RejectedByServer { cause, message },
+            },
         }
     }
 }
 
+fn mentions_iterations(text: &str) -> bool {
+    text.to_ascii_lowercase().contains("iteration")
+}
+
 /// Two-factor authentication is required.
 #[derive(Debug, Clone, PartialEq, thiserror::Error)]
 #[error("Re-authenticate with {}", enabled_providers)]
@@ -187,6 +349,7 @@ mod tests {
                 message: String::from("Google Authenticator authentication required! Update your browser extension so you can enter it."),
                 cause: String::from("googleauthrequired"),
                 enabled_providers: Some(String::from("googleauth")),
+                ..Default::default()
             }),
         };
 
@@ -213,4 +376,203 @@ mod tests {
 
         assert_eq!(got, should_be);
     }
+
+    #[test]
+    fn the_session_remembers_which_iterations_were_used() {
+        let src = include_str!("login_response_okay.xml");
+        let doc: Document = serde_xml_rs::from_str(src).unwrap();
+
+        let session = interpret_response(doc.response, 123456).unwrap();
+
+        assert_eq!(session.iterations, 123456);
+    }
+
+    fn login_error(src: &str) -> LoginError {
+        let doc: Document = serde_xml_rs::from_str(src).unwrap();
+
+        match doc.response {
+            Root::Error(err) => LoginError::from(err),
+            Root::Ok { .. } => panic!("Expected an error response"),
+        }
+    }
+
+    /// Pretend to log in, replying with each fixture in turn and recording
+    /// the iterations used for every attempt.
+    async fn fake_logins(
+        responses: &[&str],
+        iterations: usize,
+    ) -> (Result<Session, LoginError>, Vec<usize>) {
+        let mut responses = responses.iter();
+        let mut attempts = Vec::new();
+
+        let got = retry_on_iteration_mismatch(iterations, |iterations| {
+            attempts.push(iterations);
+            let src = responses.next().expect("Too many login attempts");
+            let doc: Result<Document, _> = serde_xml_rs::from_str(src);
+
+            async move { interpret_response(doc?.response, iterations) }
+        })
+        .await;
+
+        (got, attempts)
+    }
+
+    #[tokio::test]
+    async fn retry_once_with_the_suggested_iterations() {
+        let (got, attempts) = fake_logins(
+            &[
+                include_str!("login_response_iterations.xml"),
+                include_str!("login_response_okay.xml"),
+            ],
+            5000,
+        )
+        .await;
+
+        assert_eq!(attempts, &[5000, 100100]);
+        assert_eq!(got.unwrap().iterations, 100100);
+    }
+
+    #[tokio::test]
+    async fn only_retry_the_iteration_mismatch_once() {
+        let (got, attempts) = fake_logins(
+            &[
+                include_str!("login_response_iterations.xml"),
+                include_str!("login_response_iterations.xml"),
+            ],
+            5000,
+        )
+        .await;
+
+        assert_eq!(attempts, &[5000, 100100]);
+        assert!(matches!(
+            got,
+            Err(LoginError::IterationMismatch { iterations: 100100 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let (got, attempts) = fake_logins(
+            &[include_str!(
+                "login_response_unknownpassword_iterations.xml"
+            )],
+            5000,
+        )
+        .await;
+
+        assert_eq!(attempts, &[5000]);
+        assert!(matches!(got, Err(LoginError::WrongPassword { .. })));
+    }
+
+    #[test]
+    fn classify_2fa_required() {
+        let src = include_str!("login_response_googleauthrequired.xml");
+
+        let got = login_error(src);
+
+        match got {
+            LoginError::TwoFactorLoginRequired(err) => {
+                assert_eq!(err.providers().collect::<Vec<_>>(), &["googleauth"])
+            },
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn classify_unknown_email() {
+        let src = include_str!("login_response_unknownemail.xml");
+
+        let got = login_error(src);
+
+        assert!(matches!(got, LoginError::UnknownEmail { .. }));
+    }
+
+    #[test]
+    fn classify_wrong_password() {
+        let src = include_str!("login_response_unknownpassword.xml");
+
+        let got = login_error(src);
+
+        assert!(matches!(got, LoginError::WrongPassword { .. }));
+    }
+
+    #[test]
+    fn classify_iteration_mismatch() {
+        let src = include_str!("login_response_iterations.xml");
+
+        let got = login_error(src);
+
+        assert!(matches!(
+            got,
+            LoginError::IterationMismatch { iterations: 100100 }
+        ));
+    }
+
+    #[test]
+    fn a_wrong_password_is_not_an_iteration_mismatch() {
+        let src = include_str!("login_response_unknownpassword_iterations.xml");
+
+        let got = login_error(src);
+
+        assert!(matches!(got, LoginError::WrongPassword { .. }));
+    }
+
+    #[test]
+    fn classify_iteration_mismatch_with_a_cause() {
+        let err = ErrorMessage {
+            message: String::from("Incorrect number of iterations"),
+            cause: String::from("unknown"),
+            iterations: Some(5000),
+            ..Default::default()
+        };
+
+        let got = LoginError::from(err);
+
+        assert!(matches!(
+            got,
+            LoginError::IterationMismatch { iterations: 5000 }
+        ));
+    }
+
+    #[test]
+    fn classify_account_locked() {
+        let src = include_str!("login_response_accountlocked.xml");
+
+        let got = login_error(src);
+
+        assert!(matches!(got, LoginError::AccountLocked { .. }));
+    }
+
+    #[test]
+    fn classify_out_of_band_required() {
+        let src = include_str!("login_response_outofbandrequired.xml");
+
+        let got = login_error(src);
+
+        match got {
+            LoginError::OutOfBandRequired { kind, name, .. } => {
+                assert_eq!(kind, "lastpassauth");
+                assert_eq!(name, "LastPass Authenticator");
+            },
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn classify_verify_new_location() {
+        let src = include_str!("login_response_verifydevice.xml");
+
+        let got = login_error(src);
+
+        assert!(matches!(got, LoginError::VerifyNewLocation { .. }));
+    }
+
+    #[test]
+    fn classify_federated_login() {
+        let src = include_str!("login_response_federated.xml");
+
+        let got = login_error(src);
+
+        assert!(matches!(got, LoginError::Federated { .. }));
+    }
 }
@@ -1,4 +1,4 @@
-use super::EndpointError;
+use super::{EndpointError, Redacted, RetryPolicy};
 use reqwest::Client;
 use serde_derive::Serialize;
 
@@ -11,9 +11,10 @@ pub async fn logout(
     let data = Data {
         method: "cli",
         noredirect: 1,
-        token,
+        token: Redacted(token),
     };
-    super::send(client, hostname, "logout.php", &data).await?;
+    super::send(client, hostname, "logout.php", &data, &RetryPolicy::never())
+        .await?;
 
     Ok(())
 }
@@ -22,5 +23,5 @@ pub async fn logout(
 struct Data<'a> {
     method: &'a str,
     noredirect: usize,
-    token: &'a str,
+    token: Redacted<&'a str>,
 }
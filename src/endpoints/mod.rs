@@ -1,22 +1,49 @@
 //! The LastPass API's endpoints.
 
+#[cfg(feature = "unstable-attachments")]
+mod change_attachment;
+mod delete_account;
+mod diagnostics;
 mod iterations;
 mod load_attachment;
 mod login;
 mod logout;
+mod move_account;
+mod retry;
+mod save_account;
+#[cfg(feature = "unstable-attachments")]
+mod upload_attachment;
 mod vault;
 mod vault_version;
 
+#[cfg(feature = "unstable-attachments")]
+pub use change_attachment::{
+    delete_attachment, rename_attachment, ChangeAttachmentError,
+};
+pub use delete_account::{delete_account, DeleteAccountError};
 pub use iterations::iterations;
-pub use load_attachment::{load_attachment, LoadAttachmentError};
-pub use login::{login, LoginError, TwoFactorLoginRequired};
+pub use load_attachment::{
+    load_attachment, load_attachment_to, LoadAttachmentError,
+};
+pub use login::{
+    login, login_with_password, LoginError, TwoFactorLoginRequired,
+};
 pub use logout::logout;
-pub use vault::get_vault;
+pub use move_account::{move_account, MoveAccountError};
+pub use retry::{RateLimited, RetryPolicy};
+pub use save_account::{save_account, SaveAccountError};
+#[cfg(feature = "unstable-attachments")]
+pub use upload_attachment::{
+    upload_attachment, NewAttachment, UploadAttachmentError,
+};
+pub use vault::{get_vault, get_vault_blob, VaultError};
 pub use vault_version::get_vault_version;
 
-use reqwest::{Client, Error, Response};
+use bytes::Bytes;
+use diagnostics::{field_names, Call, Redacted};
+use reqwest::{Client, Error, Response, StatusCode};
 use serde::Serialize;
-use std::fmt::Debug;
+use std::future::{self, Future};
 
 /// Typical endpoint errors.
 #[derive(Debug, thiserror::Error)]
@@ -35,29 +62,202 @@ pub enum EndpointError {
     ),
     #[error("Unable to base64 decode the payload")]
     Base64(#[from] base64::DecodeError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] RateLimited),
 }
 
+impl From<SendError> for EndpointError {
+    fn from(e: SendError) -> EndpointError {
+        match e {
+            SendError::HttpClient(e) => EndpointError::HttpClient(e),
+            SendError::RateLimited(e) => EndpointError::RateLimited(e),
+        }
+    }
+}
+
+/// The ways [`send()`] can fail.
+#[derive(Debug, thiserror::Error)]
+enum SendError {
+    #[error("Unable to send the request")]
+    HttpClient(#[from] Error),
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] RateLimited),
+}
+
+/// Send a request to the LastPass server and read the response body,
+/// retrying transient failures according to the [`RetryPolicy`].
+///
+/// Only the names of the payload's fields are logged, but any secrets should
+/// still be wrapped in [`Redacted`] so they never show up in `Debug` output.
 async fn send<D>(
     client: &Client,
     hostname: &str,
     path: &str,
     data: &D,
-) -> Result<Response, Error>
+    retry: &RetryPolicy,
+) -> Result<Bytes, SendError>
+where
+    D: Serialize,
+{
+    let call = Call::start(path);
+    let result = call
+        .instrument(send_with_retries(
+            client,
+            hostname,
+            data,
+            retry,
+            &call,
+            |response| response.bytes(),
+        ))
+        .await;
+    call.finish(result.as_ref().ok().map(|body| body.len()));
+
+    result
+}
+
+/// Like [`send()`], except the response body is left for the caller to read
+/// (e.g. so it can be streamed somewhere instead of being held in memory).
+///
+/// Only failures which happen before we start receiving the body will be
+/// retried.
+async fn send_streaming<D>(
+    client: &Client,
+    hostname: &str,
+    path: &str,
+    data: &D,
+    retry: &RetryPolicy,
+) -> Result<Response, SendError>
+where
+    D: Serialize,
+{
+    let call = Call::start(path);
+    let result = call
+        .instrument(send_with_retries(
+            client,
+            hostname,
+            data,
+            retry,
+            &call,
+            |response| future::ready(Ok(response)),
+        ))
+        .await;
+    call.finish(
+        result
+            .as_ref()
+            .ok()
+            .and_then(|response| response.content_length())
+            .map(|len| len as usize),
+    );
+
+    result
+}
+
+async fn send_with_retries<D, F, Fut, T>(
+    client: &Client,
+    hostname: &str,
+    data: &D,
+    retry: &RetryPolicy,
+    call: &Call<'_>,
+    read_body: F,
+) -> Result<T, SendError>
 where
-    D: Debug + Serialize,
+    D: Serialize,
+    F: Fn(Response) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
 {
-    let url = format!("https://{}/{}", hostname, path);
+    let url = format!("https://{}/{}", hostname, call.path());
+    if log::log_enabled!(log::Level::Trace) {
+        log::trace!("Payload fields: {:?}", field_names(data));
+    }
+    let mut attempt = 0;
+
+    loop {
+        let (err, retry_after) =
+            match try_send(client, &url, data, attempt, call, &read_body).await
+            {
+                Ok(body) => return Ok(body),
+                Err(Attempt::Failed(e)) => return Err(e),
+                Err(Attempt::Transient { error, retry_after }) => {
+                    (error, retry_after)
+                },
+            };
+
+        match retry.delay(attempt, retry_after) {
+            Some(delay) => {
+                log::warn!(
+                    "Request to {} failed ({}), retrying in {:?}",
+                    url,
+                    err,
+                    delay
+                );
+                tokio::time::delay_for(delay).await;
+                attempt += 1;
+            },
+            None => return Err(err),
+        }
+    }
+}
+
+/// The outcome of a failed request.
+enum Attempt {
+    /// The request failed for a reason that retrying won't fix.
+    Failed(SendError),
+    /// The request failed, but it may succeed if we try again later.
+    Transient {
+        error: SendError,
+        retry_after: Option<std::time::Duration>,
+    },
+}
+
+impl From<Error> for Attempt {
+    fn from(e: Error) -> Attempt {
+        if retry::is_transient_error(&e) {
+            Attempt::Transient {
+                error: SendError::HttpClient(e),
+                retry_after: None,
+            }
+        } else {
+            Attempt::Failed(SendError::HttpClient(e))
+        }
+    }
+}
+
+async fn try_send<D, F, Fut, T>(
+    client: &Client,
+    url: &str,
+    data: &D,
+    attempt: u32,
+    call: &Call<'_>,
+    read_body: &F,
+) -> Result<T, Attempt>
+where
+    D: Serialize,
+    F: Fn(Response) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let request = client.post(url).form(&data).build()?;
+    call.record_request(
+        attempt,
+        request.body().and_then(|b| b.as_bytes()).map(|b| b.len()),
+    );
+
+    let response = client.execute(request).await?;
+    let status = response.status();
+    call.record_status(status);
+
+    if retry::is_transient_status(status) {
+        let retry_after = retry::retry_after(response.headers());
+        let error = if status == StatusCode::TOO_MANY_REQUESTS {
+            SendError::RateLimited(RateLimited::new(retry_after))
+        } else {
+            SendError::HttpClient(response.error_for_status().unwrap_err())
+        };
 
-    log::debug!("Sending a request to {}", url);
-    log::trace!("Payload: {:#?}", data);
-    let response = client
-        .post(&url)
-        .form(&data)
-        .send()
-        .await?
-        .error_for_status()?;
+        return Err(Attempt::Transient { error, retry_after });
+    }
 
-    log::trace!("Headers: {:#?}", response.headers());
+    let body = read_body(response.error_for_status()?).await?;
 
-    Ok(response)
+    Ok(body)
 }
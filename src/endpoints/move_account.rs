@@ -0,0 +1,265 @@
+use super::{save_account::ErrorMessage, Redacted, RetryPolicy, SendError};
+use crate::{Account, DecryptionKey, Id, Share};
+use reqwest::{Client, Error as ReqwestError};
+use serde_derive::{Deserialize, Serialize};
+
+/// Move an [`Account`] into, out of, or between shared folders.
+///
+/// Each shared folder has its own key, so the account's fields are
+/// re-encrypted with the key of the [`Share`] it is moving `to` (or the
+/// user's [`DecryptionKey`] when it leaves the shared folders altogether).
+/// Use [`Account::move_to()`] to set the destination folder first.
+///
+/// The server saves a new copy of the account and deletes the old one, so the
+/// vault should be downloaded again afterwards to learn its new [`Id`]. Moves
+/// within the same shared folder only need
+/// [`crate::endpoints::save_account()`].
+pub async fn move_account(
+    client: &Client,
+    hostname: &str,
+    token: &str,
+    account: &mut Account,
+    decryption_key: &DecryptionKey,
+    from: Option<&Share>,
+    to: Option<&Share>,
+) -> Result<(), MoveAccountError> {
+    let data = Data::new(token, account, decryption_key, from, to);
+
+    // moving deletes the original, so it isn't safe to retry
+    let body = super::send(
+        client,
+        hostname,
+        "lastpass/api.php",
+        &data,
+        &RetryPolicy::never(),
+    )
+    .await?;
+
+    let doc: Document = serde_xml_rs::from_reader(&body[..])?;
+    interpret_response(doc)?;
+
+    account.share = to.map(|share| share.id.clone());
+
+    Ok(())
+}
+
+fn interpret_response(doc: Document) -> Result<(), MoveAccountError> {
+    match doc {
+        Document {
+            result: Some(MoveResult { ref success, .. }),
+            ..
+        } if success == "1" => Ok(()),
+        Document {
+            error: Some(error), ..
+        } => Err(MoveAccountError::RejectedByServer {
+            message: error.message,
+        }),
+        Document {
+            result: Some(result),
+            ..
+        } => Err(MoveAccountError::RejectedByServer {
+            message: result.message,
+        }),
+        _ => Err(MoveAccountError::RejectedByServer {
+            message: String::new(),
+        }),
+    }
+}
+
+/// The `uploadaccounts` command, which `lastpass-cli` also uses for moving
+/// accounts between shared folders.
+#[derive(Debug, Serialize)]
+struct Data<'a> {
+    token: Redacted<&'a str>,
+    cmd: &'a str,
+    #[serde(rename = "aid0")]
+    account_id: &'a Id,
+    #[serde(rename = "name0")]
+    name: Redacted<String>,
+    #[serde(rename = "grouping0")]
+    group: Redacted<String>,
+    #[serde(rename = "url0")]
+    url: String,
+    #[serde(rename = "username0")]
+    username: Redacted<String>,
+    #[serde(rename = "password0")]
+    password: Redacted<String>,
+    #[serde(rename = "pwprotect0")]
+    password_protected: &'a str,
+    #[serde(rename = "extra0")]
+    note: Redacted<String>,
+    #[serde(rename = "todelete")]
+    to_delete: &'a Id,
+    #[serde(
+        rename = "sharedfolderid",
+        skip_serializing_if = "Option::is_none"
+    )]
+    share_id: Option<&'a Id>,
+    #[serde(
+        rename = "origsharedfolderid",
+        skip_serializing_if = "Option::is_none"
+    )]
+    original_share_id: Option<&'a Id>,
+}
+
+impl<'a> Data<'a> {
+    fn new(
+        token: &'a str,
+        account: &'a Account,
+        decryption_key: &'a DecryptionKey,
+        from: Option<&'a Share>,
+        to: Option<&'a Share>,
+    ) -> Self {
+        let key = to.map_or(decryption_key, |share| &share.key);
+        let group = to.map_or(account.group.as_str(), |share| {
+            share.relative_group(&account.group)
+        });
+
+        Data {
+            token: Redacted(token),
+            cmd: "uploadaccounts",
+            account_id: &account.id,
+            name: Redacted(key.encrypt_base64(&account.name)),
+            group: Redacted(key.encrypt_base64(group)),
+            url: hex::encode(account.url.as_str()),
+            username: Redacted(key.encrypt_base64(&account.username)),
+            password: Redacted(key.encrypt_base64(&account.password)),
+            password_protected: if account.password_protected {
+                "on"
+            } else {
+                "off"
+            },
+            note: Redacted(key.encrypt_base64(&account.note)),
+            to_delete: &account.id,
+            share_id: to.map(|share| &share.id),
+            original_share_id: from.map(|share| &share.id),
+        }
+    }
+}
+
+/// The response from `lastpass/api.php`.
+#[derive(Debug, PartialEq, Deserialize)]
+struct Document {
+    result: Option<MoveResult>,
+    error: Option<ErrorMessage>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct MoveResult {
+    #[serde(default)]
+    success: String,
+    #[serde(rename = "msg", default)]
+    message: String,
+}
+
+/// Possible errors that may be returned by [`move_account()`].
+#[derive(Debug, thiserror::Error)]
+pub enum MoveAccountError {
+    /// The HTTP client encountered an error.
+    #[error("Unable to send the request")]
+    HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] super::RateLimited),
+    /// Unable to parse the response.
+    #[error("Unable to parse the response")]
+    ResponseParse(#[from] serde_xml_rs::Error),
+    /// The server refused to move the account.
+    #[error("The server rejected the change: {}", message)]
+    RejectedByServer { message: String },
+}
+
+impl From<SendError> for MoveAccountError {
+    fn from(e: SendError) -> MoveAccountError {
+        match e {
+            SendError::HttpClient(e) => MoveAccountError::HttpClient(e),
+            SendError::RateLimited(e) => MoveAccountError::RateLimited(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn team() -> Share {
+        Share::new(
+            Id::from("42"),
+            String::from("Shared-Team"),
+            DecryptionKey::generate(),
+            false,
+        )
+    }
+
+    fn account(folder: &str) -> Account {
+        let mut account =
+            Account::new("Database", "https://example.com".parse().unwrap());
+        account.id = Id::from("1234");
+        account.password = String::from("hunter2");
+        account.move_to(folder);
+        account
+    }
+
+    #[test]
+    fn move_into_a_shared_folder() {
+        let decryption_key = DecryptionKey::generate();
+        let team = team();
+        let account = account("Shared-Team/Servers");
+
+        let got =
+            Data::new("token", &account, &decryption_key, None, Some(&team));
+
+        assert_eq!(got.share_id, Some(&team.id));
+        assert_eq!(got.original_share_id, None);
+        assert_eq!(got.to_delete, &account.id);
+        assert_eq!(team.key.decrypt_base64(&got.group.0).unwrap(), b"Servers");
+        assert_eq!(
+            team.key.decrypt_base64(&got.password.0).unwrap(),
+            b"hunter2"
+        );
+    }
+
+    #[test]
+    fn move_out_of_a_shared_folder() {
+        let decryption_key = DecryptionKey::generate();
+        let team = team();
+        let account = account("Personal");
+
+        let got =
+            Data::new("token", &account, &decryption_key, Some(&team), None);
+
+        assert_eq!(got.share_id, None);
+        assert_eq!(got.original_share_id, Some(&team.id));
+        assert_eq!(
+            decryption_key.decrypt_base64(&got.group.0).unwrap(),
+            b"Personal"
+        );
+        assert_eq!(
+            decryption_key.decrypt_base64(&got.password.0).unwrap(),
+            b"hunter2"
+        );
+    }
+
+    #[test]
+    fn parse_move_response() {
+        let src = include_str!("move_account_okay.xml");
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        assert!(interpret_response(got).is_ok());
+    }
+
+    #[test]
+    fn parse_move_error() {
+        let src = include_str!("move_account_error.xml");
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        match interpret_response(got) {
+            Err(MoveAccountError::RejectedByServer { message }) => {
+                assert_eq!(message, "Unable to move the account")
+            },
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use rand::Rng;
+use reqwest::{header::HeaderMap, StatusCode};
+use std::{
+    error::Error,
+    io::ErrorKind,
+    time::{Duration, SystemTime},
+};
+
+/// How [`crate::endpoints`] should retry a request after a transient failure
+/// (e.g. a connection reset or a `503 Service Unavailable`).
+///
+/// Retries are only ever attempted for idempotent endpoints, so things like
+/// logging in won't be sent twice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request will be retried.
+    pub max_retries: u32,
+    /// How long to wait before the first retry. This is doubled after every
+    /// subsequent attempt.
+    pub initial_backoff: Duration,
+    /// The longest we'll ever wait between attempts.
+    ///
+    /// If the server asks us to wait longer than this (via the `Retry-After`
+    /// header), we'll give up and return a [`RateLimited`] error instead.
+    pub max_backoff: Duration,
+    /// Randomise the delay between attempts so lots of clients which failed
+    /// at the same time don't all retry in lock-step.
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// A [`RetryPolicy`] which never retries.
+    pub const fn never() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            initial_backoff: Duration::from_secs(0),
+            max_backoff: Duration::from_secs(0),
+            jitter: false,
+        }
+    }
+
+    /// The exponential backoff to use before making the `attempt`'th retry
+    /// (starting from 0), ignoring jitter.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2_u32.checked_pow(attempt).unwrap_or(u32::MAX);
+
+        self.initial_backoff
+            .checked_mul(factor)
+            .map(|backoff| backoff.min(self.max_backoff))
+            .unwrap_or(self.max_backoff)
+    }
+
+    /// Figure out how long to wait before the `attempt`'th retry, taking the
+    /// server's `Retry-After` hint into account.
+    ///
+    /// Returns `None` when we shouldn't retry.
+    pub(crate) fn delay(
+        &self,
+        attempt: u32,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+
+        let mut delay = self.backoff(attempt);
+
+        if self.jitter && delay > Duration::from_secs(0) {
+            // "full jitter", see
+            // https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/
+            let millis = delay.as_millis() as u64;
+            delay = Duration::from_millis(
+                rand::thread_rng().gen_range(0, millis + 1),
+            );
+        }
+
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(delay.max(retry_after)),
+            None => Some(delay),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+/// The server indicated that we're sending too many requests.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("The server is rate limiting requests")]
+pub struct RateLimited {
+    retry_after: Option<Duration>,
+}
+
+impl RateLimited {
+    pub(crate) fn new(retry_after: Option<Duration>) -> Self {
+        RateLimited { retry_after }
+    }
+
+    /// How long the server asked us to wait before trying again, if it told
+    /// us.
+    pub fn retry_after(&self) -> Option<Duration> { self.retry_after }
+}
+
+/// Is this a status code we might get from a temporary server-side problem?
+pub(crate) fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Is this an error we might get from a temporary network problem (e.g. a
+/// timeout, being unable to connect, or the connection being dropped part way
+/// through the request)?
+///
+/// Other errors (e.g. failing to build the request) will happen every time,
+/// so there's no point retrying them.
+pub(crate) fn is_transient_error(err: &reqwest::Error) -> bool {
+    err.is_timeout()
+        || err.is_connect()
+        || (err.is_request() && is_dropped_connection(err))
+}
+
+/// Was this error caused by the other end closing the connection on us?
+///
+/// The [`std::io::Error`] is normally buried a couple of levels deep (e.g.
+/// `reqwest` wraps a `hyper` error which wraps the I/O error), so we need to
+/// walk the whole chain of sources.
+fn is_dropped_connection(err: &(dyn Error + 'static)) -> bool {
+    let mut cause = Some(err);
+
+    while let Some(err) = cause {
+        if let Some(io_error) = err.downcast_ref::<std::io::Error>() {
+            match io_error.kind() {
+                ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe => return true,
+                _ => {},
+            }
+        }
+
+        cause = err.source();
+    }
+
+    false
+}
+
+/// Parse the `Retry-After` header, which may be either a number of seconds
+/// or a HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value.trim(), SystemTime::now())
+}
+
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let when = httpdate::parse_http_date(value).ok()?;
+
+    // a date in the past means we can retry immediately
+    Some(when.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            jitter: false,
+        }
+    }
+
+    #[test]
+    fn backoff_is_exponential_and_capped() {
+        let policy = policy();
+
+        let got: Vec<_> = (0..5)
+            .map(|attempt| policy.delay(attempt, None).unwrap())
+            .collect();
+
+        assert_eq!(
+            got,
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(4),
+                Duration::from_secs(8),
+                Duration::from_secs(10),
+            ]
+        );
+        assert_eq!(policy.delay(5, None), None);
+        assert_eq!(RetryPolicy::never().delay(0, None), None);
+    }
+
+    #[test]
+    fn jitter_never_exceeds_the_backoff() {
+        let policy = RetryPolicy {
+            jitter: true,
+            ..policy()
+        };
+
+        for _ in 0..100 {
+            let delay = policy.delay(2, None).unwrap();
+            assert!(delay <= Duration::from_secs(4));
+        }
+    }
+
+    #[test]
+    fn honour_the_retry_after_hint() {
+        let policy = policy();
+
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(5))),
+            Some(Duration::from_secs(5))
+        );
+        // we give up when the server wants us to wait too long
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(60))), None);
+    }
+
+    #[test]
+    fn parse_retry_after_values() {
+        let now =
+            httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::from_secs(0))
+        );
+        assert_eq!(parse_retry_after("not a date", now), None);
+    }
+
+    /// Something like the `hyper::Error` that `reqwest` wraps around the
+    /// underlying I/O error.
+    #[derive(Debug, thiserror::Error)]
+    #[error("connection error")]
+    struct Wrapper(#[source] std::io::Error);
+
+    #[test]
+    fn dropped_connections_are_detected_through_wrappers() {
+        for kind in &[
+            ErrorKind::ConnectionReset,
+            ErrorKind::ConnectionAborted,
+            ErrorKind::BrokenPipe,
+        ] {
+            let err = Wrapper(std::io::Error::from(*kind));
+
+            assert!(is_dropped_connection(&err), "{:?}", kind);
+        }
+
+        let err = Wrapper(std::io::Error::from(ErrorKind::PermissionDenied));
+        assert!(!is_dropped_connection(&err));
+    }
+}
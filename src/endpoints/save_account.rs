@@ -0,0 +1,223 @@
+use super::{RateLimited, Redacted, RetryPolicy, SendError};
+use crate::{Account, DecryptionKey, Id, Share};
+use reqwest::{Client, Error as ReqwestError};
+use serde_derive::{Deserialize, Serialize};
+
+/// Save an [`Account`]'s details (name, folder, URL, username, password and
+/// notes) to the server.
+///
+/// All fields are encrypted using the user's [`DecryptionKey`] before they are
+/// sent, so this is also how an account gets moved to another folder (see
+/// [`Account::move_to()`]). If the server assigns the account a new [`Id`],
+/// [`Account::id`] is updated to match.
+///
+/// Accounts in a shared folder (see [`Account::share`]) need the folder's
+/// [`Share`], so their fields are encrypted with its key instead. Use
+/// [`crate::endpoints::move_account()`] to move an account into or out of a
+/// shared folder.
+pub async fn save_account(
+    client: &Client,
+    hostname: &str,
+    token: &str,
+    account: &mut Account,
+    decryption_key: &DecryptionKey,
+    share: Option<&Share>,
+) -> Result<(), SaveAccountError> {
+    let data = Data::new(token, account, decryption_key, share);
+
+    // creating a new account isn't idempotent, so we never retry
+    let body = super::send(
+        client,
+        hostname,
+        "show_website.php",
+        &data,
+        &RetryPolicy::never(),
+    )
+    .await?;
+
+    let doc: Document = serde_xml_rs::from_reader(&body[..])?;
+
+    match doc {
+        Document {
+            result: Some(result),
+            ..
+        } => {
+            if let Some(id) = result.account_id {
+                account.id = id;
+            }
+
+            Ok(())
+        },
+        Document {
+            error: Some(error), ..
+        } => Err(SaveAccountError::RejectedByServer {
+            message: error.message,
+        }),
+        _ => Err(SaveAccountError::RejectedByServer {
+            message: String::new(),
+        }),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Data<'a> {
+    extjs: usize,
+    token: Redacted<&'a str>,
+    method: &'a str,
+    #[serde(rename = "aid")]
+    account_id: &'a Id,
+    name: Redacted<String>,
+    #[serde(rename = "grouping")]
+    group: Redacted<String>,
+    url: String,
+    username: Redacted<String>,
+    password: Redacted<String>,
+    #[serde(rename = "extra")]
+    note: Redacted<String>,
+    #[serde(rename = "pwprotect")]
+    password_protected: &'a str,
+    #[serde(
+        rename = "sharedfolderid",
+        skip_serializing_if = "Option::is_none"
+    )]
+    share_id: Option<&'a Id>,
+}
+
+impl<'a> Data<'a> {
+    fn new(
+        token: &'a str,
+        account: &'a Account,
+        decryption_key: &'a DecryptionKey,
+        share: Option<&'a Share>,
+    ) -> Self {
+        let key = share.map_or(decryption_key, |share| &share.key);
+        let group = share.map_or(account.group.as_str(), |share| {
+            share.relative_group(&account.group)
+        });
+
+        Data {
+            extjs: 1,
+            token: Redacted(token),
+            method: "cli",
+            account_id: &account.id,
+            name: Redacted(key.encrypt_base64(&account.name)),
+            group: Redacted(key.encrypt_base64(group)),
+            url: hex::encode(account.url.as_str()),
+            username: Redacted(key.encrypt_base64(&account.username)),
+            password: Redacted(key.encrypt_base64(&account.password)),
+            note: Redacted(key.encrypt_base64(&account.note)),
+            password_protected: if account.password_protected {
+                "on"
+            } else {
+                "off"
+            },
+            share_id: share.map(|share| &share.id),
+        }
+    }
+}
+
+/// The response from `show_website.php`.
+#[derive(Debug, PartialEq, Deserialize)]
+pub(super) struct Document {
+    pub(super) result: Option<SaveResult>,
+    pub(super) error: Option<ErrorMessage>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub(super) struct SaveResult {
+    pub(super) action: String,
+    #[serde(rename = "aid")]
+    account_id: Option<Id>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub(super) struct ErrorMessage {
+    #[serde(rename = "msg", default)]
+    pub(super) message: String,
+}
+
+/// Possible errors that may be returned by [`save_account()`].
+#[derive(Debug, thiserror::Error)]
+pub enum SaveAccountError {
+    /// The HTTP client encountered an error.
+    #[error("Unable to send the request")]
+    HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] RateLimited),
+    /// Unable to parse the response.
+    #[error("Unable to parse the response")]
+    ResponseParse(#[from] serde_xml_rs::Error),
+    /// The server refused to save the account.
+    #[error("The server rejected the change: {}", message)]
+    RejectedByServer { message: String },
+}
+
+impl From<SendError> for SaveAccountError {
+    fn from(e: SendError) -> SaveAccountError {
+        match e {
+            SendError::HttpClient(e) => SaveAccountError::HttpClient(e),
+            SendError::RateLimited(e) => SaveAccountError::RateLimited(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_save_response() {
+        let src = include_str!("save_account_okay.xml");
+        let should_be = Document {
+            result: Some(SaveResult {
+                action: String::from("added"),
+                account_id: Some(Id::from("5496230974130180673")),
+            }),
+            error: None,
+        };
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_save_error() {
+        let src = include_str!("save_account_error.xml");
+        let should_be = Document {
+            result: None,
+            error: Some(ErrorMessage {
+                message: String::from("Unable to save the site"),
+            }),
+        };
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn accounts_in_a_share_use_its_key() {
+        let decryption_key = DecryptionKey::generate();
+        let share = Share::new(
+            Id::from("42"),
+            String::from("Shared-Team"),
+            DecryptionKey::generate(),
+            false,
+        );
+        let mut account =
+            Account::new("Database", "https://example.com".parse().unwrap());
+        account.move_to("Shared-Team/Servers");
+
+        let got = Data::new("token", &account, &decryption_key, Some(&share));
+
+        assert_eq!(got.share_id, Some(&share.id));
+        assert_eq!(share.key.decrypt_base64(&got.name.0).unwrap(), b"Database");
+        assert_eq!(share.key.decrypt_base64(&got.group.0).unwrap(), b"Servers");
+        assert_ne!(
+            decryption_key.decrypt_base64(&got.name.0).ok(),
+            Some(b"Database".to_vec())
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use super::{RateLimited, Redacted, RetryPolicy, SendError};
+use crate::{Account, Attachment, DecryptionError, DecryptionKey, Id};
+use reqwest::{Client, Error as ReqwestError};
+use serde_derive::{Deserialize, Serialize};
+
+/// Attach a file to an [`Account`].
+///
+/// The file and its name are encrypted using the account's attachment key. If
+/// the account doesn't have an attachment key yet (i.e.
+/// [`Account::encrypted_attachment_key`] is empty) a new one will be
+/// generated and uploaded alongside the file.
+///
+/// On success, the [`Account`] is updated to include the new [`Attachment`].
+///
+/// This is only available with the `unstable-attachments` feature because the
+/// `uploadattach.php` request and response formats were written by hand and
+/// haven't been confirmed against the real server.
+pub async fn upload_attachment(
+    client: &Client,
+    hostname: &str,
+    token: &str,
+    account: &mut Account,
+    decryption_key: &DecryptionKey,
+    file: &NewAttachment<'_>,
+) -> Result<Attachment, UploadAttachmentError> {
+    let NewAttachment {
+        filename,
+        mime_type,
+        content,
+    } = *file;
+
+    let (attachment_key, new_attachment_key) =
+        if account.encrypted_attachment_key.is_empty() {
+            let key = DecryptionKey::generate();
+            let encrypted = decryption_key.encrypt_base64(hex::encode(key));
+            (key, Some(encrypted))
+        } else {
+            (account.attachment_key(decryption_key)?, None)
+        };
+
+    // the attachment body gets base64-encoded before it is encrypted
+    let encrypted_data = attachment_key.encrypt_base64(base64::encode(content));
+    let encrypted_filename = attachment_key.encrypt_base64(filename);
+
+    let data = Data {
+        token: Redacted(token),
+        account_id: &account.id,
+        attachment_key: new_attachment_key.as_deref(),
+        data: Redacted(&encrypted_data),
+        filename: Redacted(&encrypted_filename),
+        mime_type,
+    };
+
+    // uploading isn't idempotent, so we never retry
+    let body = super::send(
+        client,
+        hostname,
+        "uploadattach.php",
+        &data,
+        &RetryPolicy::never(),
+    )
+    .await?;
+
+    let doc: Document = serde_xml_rs::from_reader(&body[..])?;
+
+    let attachment = Attachment {
+        id: doc.response.id,
+        parent: account.id.clone(),
+        mime_type: mime_type.to_string(),
+        storage_key: doc.response.storage_key,
+        size: content.len() as u64,
+        encrypted_filename,
+    };
+
+    if let Some(encrypted) = new_attachment_key {
+        account.encrypted_attachment_key = encrypted;
+    }
+    account.add_attachment(attachment.clone());
+
+    Ok(attachment)
+}
+
+/// A file to be attached to an [`Account`] using [`upload_attachment()`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NewAttachment<'a> {
+    /// The name the file will be shown as.
+    pub filename: &'a str,
+    /// The file's MIME type (e.g. `"text/plain"`).
+    pub mime_type: &'a str,
+    /// The file's (unencrypted) contents.
+    pub content: &'a [u8],
+}
+
+impl<'a> NewAttachment<'a> {
+    pub fn new(
+        filename: &'a str,
+        mime_type: &'a str,
+        content: &'a [u8],
+    ) -> Self {
+        NewAttachment {
+            filename,
+            mime_type,
+            content,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Data<'a> {
+    token: Redacted<&'a str>,
+    #[serde(rename = "aid")]
+    account_id: &'a Id,
+    /// The new attachment key, if we needed to generate one.
+    #[serde(rename = "attachkey", skip_serializing_if = "Option::is_none")]
+    attachment_key: Option<&'a str>,
+    data: Redacted<&'a str>,
+    filename: Redacted<&'a str>,
+    #[serde(rename = "mimetype")]
+    mime_type: &'a str,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Document {
+    #[serde(rename = "ok")]
+    response: UploadResponse,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct UploadResponse {
+    id: Id,
+    #[serde(rename = "storagekey")]
+    storage_key: String,
+}
+
+/// Possible errors that may be returned by [`upload_attachment()`].
+#[derive(Debug, thiserror::Error)]
+pub enum UploadAttachmentError {
+    /// The HTTP client encountered an error.
+    #[error("Unable to send the request")]
+    HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] RateLimited),
+    #[error("Unable to decrypt the account's attachment key")]
+    Decrypt(#[from] DecryptionError),
+    /// Unable to parse the response.
+    #[error("Unable to parse the response")]
+    ResponseParse(#[from] serde_xml_rs::Error),
+}
+
+impl From<SendError> for UploadAttachmentError {
+    fn from(e: SendError) -> UploadAttachmentError {
+        match e {
+            SendError::HttpClient(e) => UploadAttachmentError::HttpClient(e),
+            SendError::RateLimited(e) => UploadAttachmentError::RateLimited(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fixture is synthetic (written by hand rather than captured from
+    /// the server), so this only checks we read the fields we rely on.
+    #[test]
+    fn parse_upload_response() {
+        let src = include_str!("upload_attachment_okay.xml");
+        let should_be = Document {
+            response: UploadResponse {
+                id: Id::from("533903346832032070-27283"),
+                storage_key: String::from("100000027283"),
+            },
+        };
+
+        let got: Document = serde_xml_rs::from_str(src).unwrap();
+
+        assert_eq!(got, should_be);
+    }
+}
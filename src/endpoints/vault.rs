@@ -1,7 +1,9 @@
+use super::{RateLimited, RetryPolicy, SendError};
 use crate::{
     keys::{DecryptionKey, PrivateKey},
     Vault, VaultParseError,
 };
+use bytes::Bytes;
 use reqwest::{Client, Error as ReqwestError};
 use serde_derive::Serialize;
 
@@ -13,7 +15,22 @@ pub async fn get_vault(
     hostname: &str,
     decryption_key: &DecryptionKey,
     private_key: &PrivateKey,
+    retry: &RetryPolicy,
 ) -> Result<Vault, VaultError> {
+    let body = get_vault_blob(client, hostname, retry).await?;
+
+    Vault::parse(&body, decryption_key, private_key).map_err(VaultError::Parse)
+}
+
+/// Fetch the latest vault snapshot from LastPass without decrypting it.
+///
+/// This is useful for caching the vault, use [`Vault::parse()`] to decrypt
+/// it later on.
+pub async fn get_vault_blob(
+    client: &Client,
+    hostname: &str,
+    retry: &RetryPolicy,
+) -> Result<Bytes, VaultError> {
     let data = Data {
         mobile: 1,
         request_src: "cli",
@@ -22,12 +39,10 @@ pub async fn get_vault(
         has_plugin: LASTPASS_CLI_VERSION,
     };
 
-    let body = super::send(client, hostname, "getaccts.php", &data)
-        .await?
-        .bytes()
-        .await?;
+    let body =
+        super::send(client, hostname, "getaccts.php", &data, retry).await?;
 
-    Vault::parse(&body, decryption_key, private_key).map_err(VaultError::Parse)
+    Ok(body)
 }
 
 #[derive(Debug, Serialize)]
@@ -39,11 +54,25 @@ struct Data<'a> {
     has_plugin: &'a str,
 }
 
+/// Possible errors that may be returned by [`get_vault()`] and
+/// [`get_vault_blob()`].
 #[derive(Debug, thiserror::Error)]
 pub enum VaultError {
     /// The HTTP client encountered an error.
     #[error("Unable to send the request")]
     HttpClient(#[from] ReqwestError),
+    /// The server is rate limiting us.
+    #[error("Rate limited by the server")]
+    RateLimited(#[from] RateLimited),
     #[error("Unable to parse the vault")]
     Parse(#[from] VaultParseError),
 }
+
+impl From<SendError> for VaultError {
+    fn from(e: SendError) -> VaultError {
+        match e {
+            SendError::HttpClient(e) => VaultError::HttpClient(e),
+            SendError::RateLimited(e) => VaultError::RateLimited(e),
+        }
+    }
+}
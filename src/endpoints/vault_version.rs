@@ -1,4 +1,4 @@
-use super::EndpointError;
+use super::{EndpointError, RetryPolicy};
 use reqwest::Client;
 use serde_derive::{Deserialize, Serialize};
 
@@ -7,16 +7,13 @@ use serde_derive::{Deserialize, Serialize};
 pub async fn get_vault_version(
     client: &Client,
     hostname: &str,
+    retry: &RetryPolicy,
 ) -> Result<u64, EndpointError> {
     let data = Data { method: "cli" };
-    let response =
-        super::send(client, hostname, "login_check.php", &data).await?;
+    let body =
+        super::send(client, hostname, "login_check.php", &data, retry).await?;
 
-    let body = response.text().await?;
-    log::trace!("Response: {}", body);
-
-    let doc: Document = serde_xml_rs::from_str(&body)?;
-    log::trace!("Parsed response: {:#?}", doc);
+    let doc: Document = serde_xml_rs::from_reader(&body[..])?;
 
     Ok(doc.response.accounts_version)
 }
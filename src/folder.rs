@@ -0,0 +1,122 @@
+use crate::{Account, Share};
+
+/// A folder in the [`crate::Vault`]'s group hierarchy, as returned by
+/// [`crate::Vault::folders()`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Folder<'a> {
+    /// The folder's name (empty for the root folder).
+    pub name: String,
+    /// The folder's full path, using `/` to separate groups.
+    pub path: String,
+    /// Is this folder (or one of its parents) a shared folder?
+    pub shared: bool,
+    /// Any sub-folders, sorted by name.
+    pub folders: Vec<Folder<'a>>,
+    /// The accounts directly inside this folder.
+    pub accounts: Vec<&'a Account>,
+}
+
+impl<'a> Folder<'a> {
+    pub(crate) fn from_accounts<I>(accounts: I, shares: &[Share]) -> Self
+    where
+        I: IntoIterator<Item = &'a Account>,
+    {
+        let mut root = Folder::new(String::new(), String::new(), false);
+
+        // shared folders show up even when they're empty
+        for share in shares {
+            root.get_or_create(&share.name).shared = true;
+        }
+
+        for account in accounts {
+            let folder = root.get_or_create(&account.group);
+
+            // group-only records exist so empty folders still show up
+            if !account.is_group() {
+                folder.accounts.push(account);
+            }
+        }
+
+        root.sort();
+        root
+    }
+
+    fn new(name: String, path: String, shared: bool) -> Self {
+        Folder {
+            name,
+            path,
+            shared,
+            folders: Vec::new(),
+            accounts: Vec::new(),
+        }
+    }
+
+    /// Is this the root folder?
+    pub fn is_root(&self) -> bool { self.path.is_empty() }
+
+    /// Look up a sub-folder by its path. Groups may be separated by either
+    /// `/` or `\`.
+    pub fn get(&self, path: &str) -> Option<&Folder<'a>> {
+        segments(path).try_fold(self, |folder, name| {
+            folder.folders.iter().find(|child| child.name == name)
+        })
+    }
+
+    /// Iterate over this folder and all of its descendants, depth-first and
+    /// in path order.
+    pub fn walk(&self) -> impl Iterator<Item = &'_ Folder<'a>> + '_ {
+        let mut stack = vec![self];
+
+        std::iter::from_fn(move || {
+            let next = stack.pop()?;
+            stack.extend(next.folders.iter().rev());
+            Some(next)
+        })
+    }
+
+    fn get_or_create(&mut self, group: &str) -> &mut Folder<'a> {
+        let mut folder = self;
+
+        for name in segments(group) {
+            let index = match folder.folders.iter().position(|f| f.name == name)
+            {
+                Some(index) => index,
+                None => {
+                    let path = if folder.is_root() {
+                        name.to_string()
+                    } else {
+                        format!("{}/{}", folder.path, name)
+                    };
+                    let shared = folder.shared;
+                    folder.folders.push(Folder::new(
+                        name.to_string(),
+                        path,
+                        shared,
+                    ));
+                    folder.folders.len() - 1
+                },
+            };
+
+            folder = &mut folder.folders[index];
+        }
+
+        folder
+    }
+
+    fn sort(&mut self) {
+        self.folders
+            .sort_by(|left, right| left.name.cmp(&right.name));
+
+        for folder in &mut self.folders {
+            folder.sort();
+        }
+    }
+}
+
+/// Split a folder path into its groups, accepting either `/` or `\` as the
+/// separator.
+pub(crate) fn segments(path: &str) -> impl Iterator<Item = &'_ str> {
+    path.split(['/', '\\'])
+        .filter(|segment| !segment.is_empty())
+}
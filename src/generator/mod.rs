@@ -0,0 +1,508 @@
+//! Generating new passwords and passphrases.
+
+use rand::{rngs::OsRng, seq::SliceRandom, CryptoRng, Rng};
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+const VOWELS: &str = "aeiou";
+const CONSONANTS: &str = "bcdfghjklmnpqrstvwxyz";
+/// Characters which are easily mistaken for each other.
+const AMBIGUOUS: &str = "0O1lI|`'\"";
+/// The words used by [`PassphraseGenerator`], one per line.
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+/// Settings for generating a random password.
+///
+/// Passwords always contain at least one character from each of the enabled
+/// character classes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordGenerator {
+    /// How many characters the password should have.
+    pub length: usize,
+    /// Use lowercase letters.
+    pub lowercase: bool,
+    /// Use uppercase letters.
+    pub uppercase: bool,
+    /// Use the digits, `0` to `9`.
+    pub digits: bool,
+    /// Use punctuation (e.g. `!` or `#`).
+    pub symbols: bool,
+    /// Leave out characters which look alike (e.g. `0` and `O`, or `1` and
+    /// `l`).
+    pub exclude_ambiguous: bool,
+    /// Generate something which can be read aloud by alternating consonants
+    /// and vowels. This is easier to type, but a lot less random.
+    pub pronounceable: bool,
+}
+
+impl PasswordGenerator {
+    /// Generate a new password using the operating system's secure random
+    /// number generator.
+    pub fn generate(&self) -> Result<String, GeneratorError> {
+        self.generate_with(&mut OsRng)
+    }
+
+    /// Generate a new password using a particular random number generator.
+    pub fn generate_with<R>(
+        &self,
+        rng: &mut R,
+    ) -> Result<String, GeneratorError>
+    where
+        R: Rng + CryptoRng + ?Sized,
+    {
+        let classes = self.classes();
+
+        if classes.is_empty() {
+            return Err(GeneratorError::NoCharacters);
+        }
+        if self.length < classes.len() {
+            return Err(GeneratorError::TooShort {
+                length: self.length,
+                required: classes.len(),
+            });
+        }
+
+        let password = if self.pronounceable {
+            self.pronounceable_password(rng)?
+        } else {
+            random_password(&classes, self.length, rng)
+        };
+
+        Ok(password.into_iter().collect())
+    }
+
+    /// The characters in each of the enabled character classes.
+    fn classes(&self) -> Vec<Vec<char>> {
+        let enabled = [
+            (self.lowercase, LOWERCASE),
+            (self.uppercase, UPPERCASE),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ];
+
+        enabled
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, chars)| self.allowed(chars))
+            .collect()
+    }
+
+    fn allowed(&self, chars: &str) -> Vec<char> {
+        chars
+            .chars()
+            .filter(|c| !(self.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+            .collect()
+    }
+
+    /// Like [`PasswordGenerator::allowed()`], but for lowercase letters which
+    /// might be capitalised later. Uppercase-only passwords need to exclude
+    /// letters whose uppercase form is ambiguous (e.g. `o` becomes `O`).
+    fn allowed_letters(&self, letters: &str) -> Vec<char> {
+        self.allowed(letters)
+            .into_iter()
+            .filter(|c| {
+                self.lowercase
+                    || !(self.exclude_ambiguous
+                        && AMBIGUOUS.contains(c.to_ascii_uppercase()))
+            })
+            .collect()
+    }
+
+    fn pronounceable_password<R>(
+        &self,
+        rng: &mut R,
+    ) -> Result<Vec<char>, GeneratorError>
+    where
+        R: Rng + CryptoRng + ?Sized,
+    {
+        if !self.lowercase && !self.uppercase {
+            return Err(GeneratorError::NoLetters);
+        }
+
+        let consonants = self.allowed_letters(CONSONANTS);
+        let vowels = self.allowed_letters(VOWELS);
+        let starts_with_vowel = rng.gen::<bool>();
+
+        let mut password: Vec<char> = (0..self.length)
+            .map(|i| {
+                let letters = if (i % 2 == 0) == starts_with_vowel {
+                    &vowels
+                } else {
+                    &consonants
+                };
+                *letters.choose(rng).expect("Never empty")
+            })
+            .collect();
+
+        if !self.lowercase {
+            password
+                .iter_mut()
+                .for_each(|c| *c = c.to_ascii_uppercase());
+        } else if self.uppercase {
+            // capitalise one of the letters which won't become ambiguous
+            let candidates: Vec<usize> = (0..password.len())
+                .filter(|&i| {
+                    let upper = password[i].to_ascii_uppercase();
+                    !(self.exclude_ambiguous && AMBIGUOUS.contains(upper))
+                })
+                .collect();
+            if let Some(&i) = candidates.choose(rng) {
+                password[i] = password[i].to_ascii_uppercase();
+            }
+        }
+
+        // the remaining classes replace letters at random (but distinct)
+        // positions, steering clear of the letter we just capitalised
+        let mut positions: Vec<usize> = (0..password.len())
+            .filter(|&i| password[i].is_ascii_lowercase() || !self.lowercase)
+            .collect();
+        positions.shuffle(rng);
+
+        for (enabled, chars) in
+            &[(self.digits, DIGITS), (self.symbols, SYMBOLS)]
+        {
+            if !enabled {
+                continue;
+            }
+            let position = positions.pop().ok_or(GeneratorError::TooShort {
+                length: self.length,
+                required: self.classes().len(),
+            })?;
+            password[position] =
+                *self.allowed(chars).choose(rng).expect("Never empty");
+        }
+
+        Ok(password)
+    }
+}
+
+impl Default for PasswordGenerator {
+    fn default() -> Self {
+        PasswordGenerator {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+            pronounceable: false,
+        }
+    }
+}
+
+fn random_password<R>(
+    classes: &[Vec<char>],
+    length: usize,
+    rng: &mut R,
+) -> Vec<char>
+where
+    R: Rng + CryptoRng + ?Sized,
+{
+    let everything: Vec<char> = classes.iter().flatten().copied().collect();
+
+    // one from each class, then fill the rest from the whole lot
+    let mut password: Vec<char> = classes
+        .iter()
+        .map(|chars| *chars.choose(rng).expect("Never empty"))
+        .collect();
+    while password.len() < length {
+        password.push(*everything.choose(rng).expect("Never empty"));
+    }
+    password.shuffle(rng);
+
+    password
+}
+
+/// Settings for generating a diceware-style passphrase made of random words
+/// (e.g. `"tulip-anvil-quiet-harbor-mango-zest"`).
+///
+/// Each word is picked from a bundled list of about 1500 short English words,
+/// adding roughly 10.5 bits of entropy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassphraseGenerator {
+    /// How many words to use.
+    pub words: usize,
+    /// What to put between each word.
+    pub separator: String,
+    /// Capitalise the first letter of each word.
+    pub capitalize: bool,
+}
+
+impl PassphraseGenerator {
+    /// Generate a new passphrase using the operating system's secure random
+    /// number generator.
+    pub fn generate(&self) -> String { self.generate_with(&mut OsRng) }
+
+    /// Generate a new passphrase using a particular random number generator.
+    pub fn generate_with<R>(&self, rng: &mut R) -> String
+    where
+        R: Rng + CryptoRng + ?Sized,
+    {
+        let wordlist: Vec<&str> = wordlist().collect();
+
+        (0..self.words)
+            .map(|_| {
+                let word = *wordlist.choose(rng).expect("Never empty");
+                if self.capitalize {
+                    capitalize(word)
+                } else {
+                    word.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(&self.separator)
+    }
+}
+
+impl Default for PassphraseGenerator {
+    fn default() -> Self {
+        PassphraseGenerator {
+            words: 6,
+            separator: String::from("-"),
+            capitalize: false,
+        }
+    }
+}
+
+fn wordlist() -> impl Iterator<Item = &'static str> {
+    WORDLIST.lines().filter(|line| !line.is_empty())
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Reasons a [`PasswordGenerator`] may be unable to create a password.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum GeneratorError {
+    /// None of the character classes were enabled.
+    #[error("At least one character class must be enabled")]
+    NoCharacters,
+    /// Pronounceable passwords need lowercase or uppercase letters.
+    #[error("Pronounceable passwords need letters")]
+    NoLetters,
+    /// The password is too short to contain a character from each class.
+    #[error(
+        "A password needs at least {} characters to include every character class, not {}",
+        required,
+        length
+    )]
+    TooShort { length: usize, required: usize },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::HashSet;
+
+    fn rng() -> StdRng { StdRng::seed_from_u64(42) }
+
+    #[test]
+    fn passwords_have_the_right_length() {
+        let mut rng = rng();
+
+        for length in &[4, 8, 20, 100] {
+            let generator = PasswordGenerator {
+                length: *length,
+                ..Default::default()
+            };
+
+            let password = generator.generate_with(&mut rng).unwrap();
+
+            assert_eq!(password.chars().count(), *length);
+        }
+    }
+
+    #[test]
+    fn every_enabled_class_is_used() {
+        let mut rng = rng();
+        let generator = PasswordGenerator {
+            length: 4,
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let password = generator.generate_with(&mut rng).unwrap();
+
+            assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+            assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+            assert!(password.chars().any(|c| c.is_ascii_digit()));
+            assert!(password.chars().any(|c| SYMBOLS.contains(c)));
+        }
+    }
+
+    #[test]
+    fn disabled_classes_are_never_used() {
+        let mut rng = rng();
+        let generator = PasswordGenerator {
+            length: 64,
+            symbols: false,
+            uppercase: false,
+            ..Default::default()
+        };
+
+        let password = generator.generate_with(&mut rng).unwrap();
+
+        assert!(password
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn ambiguous_characters_can_be_excluded() {
+        let mut rng = rng();
+
+        for pronounceable in &[false, true] {
+            let generator = PasswordGenerator {
+                length: 200,
+                exclude_ambiguous: true,
+                pronounceable: *pronounceable,
+                ..Default::default()
+            };
+
+            let password = generator.generate_with(&mut rng).unwrap();
+
+            assert!(!password.chars().any(|c| AMBIGUOUS.contains(c)));
+        }
+    }
+
+    #[test]
+    fn uppercase_pronounceable_passwords_exclude_ambiguous_letters() {
+        let mut rng = rng();
+        let generator = PasswordGenerator {
+            length: 200,
+            lowercase: false,
+            uppercase: true,
+            exclude_ambiguous: true,
+            pronounceable: true,
+            ..Default::default()
+        };
+
+        for _ in 0..20 {
+            let password = generator.generate_with(&mut rng).unwrap();
+
+            assert!(!password.chars().any(|c| AMBIGUOUS.contains(c)));
+            assert!(!password.chars().any(|c| c.is_ascii_lowercase()));
+        }
+    }
+
+    #[test]
+    fn pronounceable_passwords_alternate_consonants_and_vowels() {
+        let mut rng = rng();
+        let generator = PasswordGenerator {
+            length: 12,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            pronounceable: true,
+            ..Default::default()
+        };
+
+        let password: Vec<char> =
+            generator.generate_with(&mut rng).unwrap().chars().collect();
+
+        for pair in password.windows(2) {
+            assert_ne!(VOWELS.contains(pair[0]), VOWELS.contains(pair[1]));
+        }
+    }
+
+    #[test]
+    fn pronounceable_passwords_still_use_every_class() {
+        let mut rng = rng();
+        let generator = PasswordGenerator {
+            length: 4,
+            pronounceable: true,
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let password = generator.generate_with(&mut rng).unwrap();
+
+            assert_eq!(password.len(), 4);
+            assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+            assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+            assert!(password.chars().any(|c| c.is_ascii_digit()));
+            assert!(password.chars().any(|c| SYMBOLS.contains(c)));
+        }
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let nothing = PasswordGenerator {
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            ..Default::default()
+        };
+        assert_eq!(nothing.generate(), Err(GeneratorError::NoCharacters));
+
+        let too_short = PasswordGenerator {
+            length: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            too_short.generate(),
+            Err(GeneratorError::TooShort {
+                length: 3,
+                required: 4
+            })
+        );
+
+        let no_letters = PasswordGenerator {
+            lowercase: false,
+            uppercase: false,
+            pronounceable: true,
+            ..Default::default()
+        };
+        assert_eq!(no_letters.generate(), Err(GeneratorError::NoLetters));
+    }
+
+    #[test]
+    fn passphrases_use_words_from_the_list() {
+        let mut rng = rng();
+        let words: HashSet<&str> = wordlist().collect();
+        let generator = PassphraseGenerator::default();
+
+        let passphrase = generator.generate_with(&mut rng);
+
+        let got: Vec<&str> = passphrase.split('-').collect();
+        assert_eq!(got.len(), 6);
+        assert!(got.iter().all(|word| words.contains(word)));
+    }
+
+    #[test]
+    fn capitalised_passphrases() {
+        let mut rng = rng();
+        let generator = PassphraseGenerator {
+            words: 3,
+            separator: String::from(" "),
+            capitalize: true,
+        };
+
+        let passphrase = generator.generate_with(&mut rng);
+
+        for word in passphrase.split(' ') {
+            assert!(word.chars().next().unwrap().is_ascii_uppercase());
+        }
+    }
+
+    #[test]
+    fn the_wordlist_is_sorted_and_unique() {
+        let words: Vec<&str> = wordlist().collect();
+
+        assert!(words.len() > 1024);
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(words
+            .iter()
+            .all(|word| word.chars().all(|c| c.is_ascii_lowercase())));
+    }
+}
@@ -1,7 +1,11 @@
+use serde_derive::{Deserialize, Serialize};
 use std::{ops::Deref, str::FromStr};
 
 /// A unique resource identifier.
-#[derive(Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
+#[derive(
+    Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
 pub struct Id(String);
 
 impl<S: Into<String>> From<S> for Id {
@@ -4,6 +4,7 @@ use base64::DecodeError;
 use block_modes::{block_padding::Pkcs7, BlockMode, Cbc, Ecb};
 use digest::Digest;
 use hmac::Hmac;
+use rand::{rngs::OsRng, RngCore};
 use sha2::Sha256;
 use std::{
     fmt::{self, Debug, Formatter},
@@ -41,6 +42,15 @@ impl DecryptionKey {
         Ok(DecryptionKey::from_raw(buffer))
     }
 
+    /// Generate a new random key (e.g. for encrypting an account's
+    /// attachments).
+    pub fn generate() -> Self {
+        let mut buffer = [0; Self::LEN];
+        OsRng.fill_bytes(&mut buffer);
+
+        DecryptionKey::from_raw(buffer)
+    }
+
     pub fn calculate(
         username: &str,
         password: &str,
@@ -90,7 +100,7 @@ impl DecryptionKey {
             let iv = &ciphertext[1..17];
             let ciphertext = &ciphertext[17..];
 
-            Cbc::<Aes256, Pkcs7>::new_var(&self.0, &iv)?
+            Cbc::<Aes256, Pkcs7>::new_var(&self.0, iv)?
                 .decrypt_vec(ciphertext)?
         } else {
             Ecb::<Aes256, Pkcs7>::new_var(&self.0, &[])?
@@ -109,6 +119,45 @@ impl DecryptionKey {
 
         Ok(decrypted)
     }
+
+    /// Encrypt some data using AES-256 in CBC mode with a random IV.
+    ///
+    /// The result is in the same format [`DecryptionKey::decrypt()`] expects,
+    /// a `!` followed by the IV and the ciphertext.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut iv = [0; 16];
+        OsRng.fill_bytes(&mut iv);
+
+        let ciphertext = self.encrypt_with_iv(plaintext, &iv);
+
+        let mut buffer = Vec::with_capacity(1 + iv.len() + ciphertext.len());
+        buffer.push(b'!');
+        buffer.extend_from_slice(&iv);
+        buffer.extend_from_slice(&ciphertext);
+
+        buffer
+    }
+
+    /// Encrypt some data using AES-256 in CBC mode, returning just the
+    /// ciphertext.
+    pub(crate) fn encrypt_with_iv(
+        &self,
+        plaintext: &[u8],
+        iv: &[u8],
+    ) -> Vec<u8> {
+        Cbc::<Aes256, Pkcs7>::new_var(&self.0, iv)
+            .expect("The key and IV are always the right length")
+            .encrypt_vec(plaintext)
+    }
+
+    /// Encrypt some data, encoding it in the `"!<iv>|<ciphertext>"` base64
+    /// format used by [`DecryptionKey::decrypt_base64()`].
+    pub fn encrypt_base64<P: AsRef<[u8]>>(&self, plaintext: P) -> String {
+        let encrypted = self.encrypt(plaintext.as_ref());
+        let (iv, ciphertext) = encrypted[1..].split_at(16);
+
+        format!("!{}|{}", base64::encode(iv), base64::encode(ciphertext))
+    }
 }
 
 fn uses_cbc(ciphertext: &[u8]) -> bool {
@@ -233,4 +282,29 @@ mod tests {
             "Example password without folder"
         );
     }
+
+    #[test]
+    fn round_trip_through_encryption() {
+        let key = DecryptionKey::generate();
+        let plaintext = b"Hello, World!";
+
+        let encrypted = key.encrypt(plaintext);
+        let got = key.decrypt(&encrypted).unwrap();
+
+        assert!(uses_cbc(&encrypted));
+        assert_eq!(got, plaintext);
+    }
+
+    #[test]
+    fn round_trip_through_base64_encryption() {
+        let key = DecryptionKey::generate();
+        let plaintext = "My Super Secret Password!";
+
+        let encrypted = key.encrypt_base64(plaintext);
+        let got = key.decrypt_base64(&encrypted).unwrap();
+
+        assert!(encrypted.starts_with('!'));
+        assert!(encrypted.contains('|'));
+        assert_eq!(got, plaintext.as_bytes());
+    }
 }
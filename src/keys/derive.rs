@@ -0,0 +1,81 @@
+use crate::keys::{DecryptionKey, LoginKey};
+
+/// Calculate both the [`LoginKey`] and [`DecryptionKey`] for a user.
+///
+/// Calculating the keys involves running PBKDF2 for the requested number of
+/// iterations (typically 100,000 or more), but the [`DecryptionKey`] is the
+/// first pass of the [`LoginKey`] calculation so we only need to do the
+/// expensive part once.
+pub fn derive_keys(
+    username: &str,
+    password: &str,
+    iterations: usize,
+) -> (LoginKey, DecryptionKey) {
+    let decryption_key =
+        DecryptionKey::calculate(username, password, iterations);
+    let login_key =
+        LoginKey::from_decryption_key(&decryption_key, password, iterations);
+
+    (login_key, decryption_key)
+}
+
+/// An async version of [`derive_keys()`] which runs the calculation on
+/// `tokio`'s blocking thread pool so it won't stall the executor.
+///
+/// # Panics
+///
+/// This must be called from within a `tokio` runtime.
+pub async fn derive_keys_async(
+    username: &str,
+    password: &str,
+    iterations: usize,
+) -> (LoginKey, DecryptionKey) {
+    let username = username.to_string();
+    let password = password.to_string();
+
+    let result = tokio::task::spawn_blocking(move || {
+        derive_keys(&username, &password, iterations)
+    })
+    .await;
+
+    match result {
+        Ok(keys) => keys,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => panic!("Key derivation was cancelled: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USERNAME: &str = "MichaelFBryan@gmail.com";
+    const PASSWORD: &str = "My Super Secret Password!";
+
+    #[test]
+    fn derived_keys_match_the_individual_calculations() {
+        for &iterations in &[1, 100] {
+            let (login_key, decryption_key) =
+                derive_keys(USERNAME, PASSWORD, iterations);
+
+            assert_eq!(
+                login_key,
+                LoginKey::calculate(USERNAME, PASSWORD, iterations)
+            );
+            assert_eq!(
+                decryption_key,
+                DecryptionKey::calculate(USERNAME, PASSWORD, iterations)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn derive_keys_on_the_blocking_thread_pool() {
+        let should_be = derive_keys(USERNAME, PASSWORD, 100);
+
+        let got = derive_keys_async(USERNAME, PASSWORD, 100).await;
+
+        assert_eq!(got.0, should_be.0);
+        assert_eq!(got.1, should_be.1);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::keys::DecryptionKey;
 use digest::Digest;
 use hmac::Hmac;
 use sha2::Sha256;
@@ -14,17 +15,34 @@ impl LoginKey {
     pub const LEN: usize = crate::keys::KDF_HASH_LEN * 2;
 
     /// Calculate a new [`LoginKey`].
+    ///
+    /// If you also need the [`DecryptionKey`], use [`crate::derive_keys()`]
+    /// to avoid doing the expensive part of the calculation twice.
     pub fn calculate(
         username: &str,
         password: &str,
         iterations: usize,
     ) -> Self {
-        let username = username.to_lowercase();
+        let decryption_key =
+            DecryptionKey::calculate(username, password, iterations);
 
+        LoginKey::from_decryption_key(&decryption_key, password, iterations)
+    }
+
+    /// Derive the [`LoginKey`] from a [`DecryptionKey`] which was calculated
+    /// using the same password and number of iterations.
+    ///
+    /// The [`DecryptionKey`] is the first pass of the [`LoginKey`]
+    /// calculation, so this is almost free.
+    pub fn from_decryption_key(
+        decryption_key: &DecryptionKey,
+        password: &str,
+        iterations: usize,
+    ) -> Self {
         if iterations <= 1 {
-            LoginKey::sha256(&username, password)
+            LoginKey::sha256(decryption_key, password)
         } else {
-            LoginKey::pbkdf2(&username, password, iterations)
+            LoginKey::pbkdf2(decryption_key, password)
         }
     }
 
@@ -34,9 +52,8 @@ impl LoginKey {
             .expect("The calculation process ensures this is a hex string")
     }
 
-    fn sha256(username: &str, password: &str) -> Self {
-        let first_pass = Sha256::new().chain(username).chain(password).result();
-        let first_pass_hex = hex::encode(&first_pass);
+    fn sha256(first_pass: &DecryptionKey, password: &str) -> Self {
+        let first_pass_hex = hex::encode(first_pass);
 
         let second_pass = Sha256::new()
             .chain(&first_pass_hex)
@@ -46,22 +63,13 @@ impl LoginKey {
         LoginKey::from_bytes(&second_pass)
     }
 
-    fn pbkdf2(username: &str, password: &str, iterations: usize) -> Self {
-        // the first rearranges the password (maintaining length), salting it
-        // with the username
-        let mut first_pass = [0; crate::keys::KDF_HASH_LEN];
-        pbkdf2::pbkdf2::<Hmac<Sha256>>(
-            password.as_bytes(),
-            username.as_bytes(),
-            iterations,
-            &mut first_pass,
-        );
-
-        // we then hash the previous key, salting with the password
-        // previous key
+    fn pbkdf2(first_pass: &DecryptionKey, password: &str) -> Self {
+        // the first pass (i.e. the decryption key) rearranges the password
+        // (maintaining length), salting it with the username. We then hash
+        // the previous key, salting with the password
         let mut key = [0; crate::keys::KDF_HASH_LEN];
         pbkdf2::pbkdf2::<Hmac<Sha256>>(
-            &first_pass,
+            first_pass,
             password.as_bytes(),
             1,
             &mut key,
@@ -118,7 +126,7 @@ mod tests {
         let should_be =
             LoginKey(*b"b8a31d9784fa9a263d0e7a0d866b70612687f7067733126d74ccde02d3bab494");
 
-        let got = LoginKey::sha256(username, password);
+        let got = LoginKey::calculate(username, password, 1);
 
         assert_eq!(got, should_be);
     }
@@ -131,7 +139,7 @@ mod tests {
         let should_be =
             LoginKey(*b"f93111b2fb6699de187ef8307aa84b1e9fdabf4a46cb821e83e507a95c3f7c97");
 
-        let got = LoginKey::pbkdf2(username, password, iterations);
+        let got = LoginKey::calculate(username, password, iterations);
 
         assert_eq!(got, should_be);
     }
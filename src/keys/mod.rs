@@ -1,16 +1,22 @@
 //! Key management.
 
 mod decryption_key;
+mod derive;
 mod login_key;
 mod private_key;
+#[cfg(test)]
+pub(crate) use private_key::tests;
+mod stream;
 
 const SHA256_LEN: usize =
     <<sha2::Sha256 as digest::FixedOutput>::OutputSize as typenum::marker_traits::Unsigned>::USIZE;
 const KDF_HASH_LEN: usize = SHA256_LEN;
 
 pub use decryption_key::DecryptionKey;
+pub use derive::{derive_keys, derive_keys_async};
 pub use login_key::LoginKey;
 pub use private_key::PrivateKey;
+pub(crate) use stream::StreamDecryptor;
 
 /// Errors that are returned when decryption fails.
 #[derive(Debug, thiserror::Error)]
@@ -23,4 +29,10 @@ pub enum DecryptionError {
     Base64(#[from] base64::DecodeError),
     #[error("Unable to create a key from its hex representation")]
     Hex(#[from] hex::FromHexError),
+    #[error("The decrypted text isn't valid UTF-8")]
+    InvalidUtf8(#[from] std::string::FromUtf8Error),
+    #[error("The private key isn't valid")]
+    InvalidPrivateKey,
+    #[error("RSA decryption failed")]
+    Rsa(#[from] rsa::errors::Error),
 }
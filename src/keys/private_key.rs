@@ -1,14 +1,68 @@
+use crate::keys::{DecryptionError, DecryptionKey};
+use rsa::{PaddingScheme, RSAPrivateKey};
 use std::{
     fmt::{self, Debug, Formatter},
     str::FromStr,
 };
 
+const PREFIX: &str = "LastPassPrivateKey<";
+const SUFFIX: &str = ">LastPassPrivateKey";
+
 /// A private key that can be used to decrypt items in the password vault.
+///
+/// This is the (still encrypted) RSA key the server sends when logging in. It
+/// needs the user's [`DecryptionKey`] before it can be used.
 #[derive(Clone, PartialEq)]
 pub struct PrivateKey(Vec<u8>);
 
 impl PrivateKey {
     pub fn new<V: Into<Vec<u8>>>(key: V) -> Self { PrivateKey(key.into()) }
+
+    pub fn as_bytes(&self) -> &[u8] { &self.0 }
+
+    /// Decrypt something which was encrypted with the user's public key (e.g.
+    /// a shared folder's key).
+    pub fn decrypt(
+        &self,
+        decryption_key: &DecryptionKey,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, DecryptionError> {
+        let rsa_key = self.rsa_key(decryption_key)?;
+        let plaintext = rsa_key
+            .decrypt(PaddingScheme::new_oaep::<sha1::Sha1>(), ciphertext)?;
+
+        Ok(plaintext)
+    }
+
+    fn rsa_key(
+        &self,
+        decryption_key: &DecryptionKey,
+    ) -> Result<RSAPrivateKey, DecryptionError> {
+        if self.0.is_empty() {
+            return Err(DecryptionError::InvalidPrivateKey);
+        }
+
+        // The key is encrypted using AES-256 in CBC mode, with the first 16
+        // bytes of the decryption key as its IV
+        let mut encrypted = Vec::with_capacity(1 + 16 + self.0.len());
+        encrypted.push(b'!');
+        encrypted.extend_from_slice(&decryption_key[..16]);
+        encrypted.extend_from_slice(&self.0);
+
+        let decrypted = String::from_utf8(decryption_key.decrypt(&encrypted)?)?;
+        let start = decrypted
+            .find(PREFIX)
+            .ok_or(DecryptionError::InvalidPrivateKey)?
+            + PREFIX.len();
+        let end = decrypted
+            .find(SUFFIX)
+            .filter(|&end| end >= start)
+            .ok_or(DecryptionError::InvalidPrivateKey)?;
+        let der = hex::decode(&decrypted[start..end])?;
+
+        RSAPrivateKey::from_pkcs8(&der)
+            .map_err(|_| DecryptionError::InvalidPrivateKey)
+    }
 }
 
 impl FromStr for PrivateKey {
@@ -24,3 +78,59 @@ impl Debug for PrivateKey {
         f.debug_tuple("PrivateKey").field(&"<redacted>").finish()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use rsa::PublicKey;
+
+    /// A throwaway 1024-bit RSA key, generated with `openssl genpkey` and
+    /// hex-encoded as PKCS #8 DER (the format LastPass uses).
+    pub(crate) const TEST_KEY: &str = include_str!("test_private_key.hex");
+
+    /// Encrypt the [`TEST_KEY`] the same way LastPass does.
+    pub(crate) fn private_key(decryption_key: &DecryptionKey) -> PrivateKey {
+        let wrapped = format!("{}{}{}", PREFIX, TEST_KEY.trim(), SUFFIX);
+        let encrypted = decryption_key
+            .encrypt_with_iv(wrapped.as_bytes(), &decryption_key[..16]);
+
+        PrivateKey::new(encrypted)
+    }
+
+    /// Encrypt something with the [`TEST_KEY`]'s public half.
+    pub(crate) fn encrypt(plaintext: &[u8]) -> Vec<u8> {
+        let der = hex::decode(TEST_KEY.trim()).unwrap();
+        let public_key =
+            RSAPrivateKey::from_pkcs8(&der).unwrap().to_public_key();
+
+        public_key
+            .encrypt(
+                &mut OsRng,
+                PaddingScheme::new_oaep::<sha1::Sha1>(),
+                plaintext,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn decrypt_with_the_private_key() {
+        let decryption_key = DecryptionKey::generate();
+        let private_key = private_key(&decryption_key);
+        let ciphertext = encrypt(b"Hello, World!");
+
+        let got = private_key.decrypt(&decryption_key, &ciphertext).unwrap();
+
+        assert_eq!(got, b"Hello, World!");
+    }
+
+    #[test]
+    fn the_wrong_decryption_key_is_an_error() {
+        let private_key = private_key(&DecryptionKey::generate());
+        let ciphertext = encrypt(b"Hello, World!");
+
+        let got = private_key.decrypt(&DecryptionKey::generate(), &ciphertext);
+
+        assert!(got.is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use crate::keys::{DecryptionError, DecryptionKey};
+use aes::Aes256;
+use base64::DecodeError;
+use block_modes::{
+    block_padding::{NoPadding, Pkcs7},
+    BlockMode, Cbc, Ecb,
+};
+
+const BLOCK_SIZE: usize = 16;
+
+/// Incrementally decrypts an attachment as it is downloaded.
+///
+/// Attachments are base64-encoded, encrypted, then base64-encoded again (in
+/// the format accepted by [`DecryptionKey::decrypt_base64()`]), so this
+/// undoes each step on the fly without needing the whole ciphertext in
+/// memory.
+#[derive(Debug)]
+pub(crate) struct StreamDecryptor {
+    key: DecryptionKey,
+    mode: Mode,
+    ciphertext_decoder: Base64Decoder,
+    /// Ciphertext which hasn't been decrypted yet.
+    ciphertext: Vec<u8>,
+    plaintext_decoder: Base64Decoder,
+}
+
+#[derive(Debug)]
+enum Mode {
+    /// We're still reading the header and don't know which mode is used.
+    Unknown(Vec<u8>),
+    Cbc {
+        /// The previous ciphertext block (initially the IV).
+        previous: [u8; BLOCK_SIZE],
+    },
+    Ecb,
+}
+
+impl StreamDecryptor {
+    pub(crate) fn new(key: DecryptionKey) -> Self {
+        StreamDecryptor {
+            key,
+            mode: Mode::Unknown(Vec::new()),
+            ciphertext_decoder: Base64Decoder::default(),
+            ciphertext: Vec::new(),
+            plaintext_decoder: Base64Decoder::default(),
+        }
+    }
+
+    /// Feed the next chunk of the response body into the decryptor, writing
+    /// any decrypted bytes to `output`.
+    pub(crate) fn feed(
+        &mut self,
+        chunk: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), DecryptionError> {
+        let chunk = match self.mode {
+            Mode::Unknown(ref mut header) => {
+                header.extend_from_slice(chunk);
+
+                match read_header(header)? {
+                    Some((mode, rest)) => {
+                        let rest = rest.to_vec();
+                        self.mode = mode;
+                        rest
+                    },
+                    None => return Ok(()),
+                }
+            },
+            _ => chunk.to_vec(),
+        };
+
+        self.ciphertext_decoder.feed(&chunk, &mut self.ciphertext)?;
+
+        // always hold back the last block, it contains the padding
+        let ready =
+            self.ciphertext.len().saturating_sub(1) / BLOCK_SIZE * BLOCK_SIZE;
+
+        if ready > 0 {
+            let mut blocks: Vec<u8> = self.ciphertext.drain(..ready).collect();
+            let plaintext = self.decrypt_blocks::<NoPadding>(&mut blocks)?;
+            self.plaintext_decoder.feed(plaintext, output)?;
+        }
+
+        Ok(())
+    }
+
+    /// Signal that the response body has finished, writing any remaining
+    /// bytes to `output`.
+    pub(crate) fn finish(
+        mut self,
+        output: &mut Vec<u8>,
+    ) -> Result<(), DecryptionError> {
+        if let Mode::Unknown(ref header) = self.mode {
+            if header.starts_with(b"!") {
+                // we never found the end of the IV
+                return Err(DecodeError::InvalidLength.into());
+            }
+
+            let header = header.clone();
+            self.mode = Mode::Ecb;
+            self.ciphertext_decoder
+                .feed(&header, &mut self.ciphertext)?;
+        }
+
+        let decoder = std::mem::take(&mut self.ciphertext_decoder);
+        decoder.finish(&mut self.ciphertext)?;
+
+        if !self.ciphertext.is_empty() {
+            let mut blocks = std::mem::take(&mut self.ciphertext);
+            let plaintext = self.decrypt_blocks::<Pkcs7>(&mut blocks)?;
+            self.plaintext_decoder.feed(plaintext, output)?;
+        }
+
+        self.plaintext_decoder.finish(output)?;
+
+        Ok(())
+    }
+
+    fn decrypt_blocks<'a, P>(
+        &mut self,
+        blocks: &'a mut [u8],
+    ) -> Result<&'a [u8], DecryptionError>
+    where
+        P: block_modes::block_padding::Padding,
+    {
+        match self.mode {
+            Mode::Cbc { ref mut previous } => {
+                let iv = *previous;
+                previous.copy_from_slice(&blocks[blocks.len() - BLOCK_SIZE..]);
+
+                Ok(Cbc::<Aes256, P>::new_var(&self.key, &iv)?
+                    .decrypt(blocks)?)
+            },
+            Mode::Ecb => {
+                Ok(Ecb::<Aes256, P>::new_var(&self.key, &[])?
+                    .decrypt(blocks)?)
+            },
+            Mode::Unknown(_) => unreachable!(),
+        }
+    }
+}
+
+/// Try to figure out which [`Mode`] is being used, returning the mode and
+/// any bytes after the header.
+///
+/// CBC ciphertext looks like `"!<base64 IV>|<base64 ciphertext>"`, while ECB
+/// ciphertext is plain base64.
+fn read_header(header: &[u8]) -> Result<Option<(Mode, &[u8])>, DecodeError> {
+    if header.is_empty() {
+        return Ok(None);
+    }
+
+    if !header.starts_with(b"!") {
+        return Ok(Some((Mode::Ecb, header)));
+    }
+
+    let pipe = match header.iter().position(|&b| b == b'|') {
+        Some(pipe) => pipe,
+        None => return Ok(None),
+    };
+
+    let iv = base64::decode(&header[1..pipe])?;
+
+    if iv.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidLength);
+    }
+
+    let mut previous = [0; BLOCK_SIZE];
+    previous.copy_from_slice(&iv);
+
+    Ok(Some((Mode::Cbc { previous }, &header[pipe + 1..])))
+}
+
+/// A base64 decoder which accepts its input in arbitrarily sized chunks.
+#[derive(Debug, Default)]
+struct Base64Decoder {
+    pending: Vec<u8>,
+}
+
+impl Base64Decoder {
+    fn feed(
+        &mut self,
+        input: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), DecodeError> {
+        self.pending
+            .extend(input.iter().copied().filter(|b| !b.is_ascii_whitespace()));
+
+        // base64 works in groups of 4 characters
+        let usable = self.pending.len() / 4 * 4;
+        base64::decode_config_buf(
+            &self.pending[..usable],
+            base64::STANDARD,
+            output,
+        )?;
+        self.pending.drain(..usable);
+
+        Ok(())
+    }
+
+    fn finish(self, output: &mut Vec<u8>) -> Result<(), DecodeError> {
+        base64::decode_config_buf(&self.pending, base64::STANDARD, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decrypt_in_chunks(
+        key: DecryptionKey,
+        ciphertext: &[u8],
+        chunk_size: usize,
+    ) -> Vec<u8> {
+        let mut decryptor = StreamDecryptor::new(key);
+        let mut output = Vec::new();
+
+        for chunk in ciphertext.chunks(chunk_size) {
+            decryptor.feed(chunk, &mut output).unwrap();
+        }
+        decryptor.finish(&mut output).unwrap();
+
+        output
+    }
+
+    fn file_contents() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn decrypt_cbc_in_chunks() {
+        let key = DecryptionKey::generate();
+        let contents = file_contents();
+        let ciphertext = key.encrypt_base64(base64::encode(&contents));
+
+        for &chunk_size in &[1, 3, 16, 17, 100, 10_000] {
+            let got = decrypt_in_chunks(key, ciphertext.as_bytes(), chunk_size);
+
+            assert_eq!(got, contents, "Chunk size: {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn decrypt_ecb_in_chunks() {
+        let key = DecryptionKey::generate();
+        let contents = file_contents();
+        let encrypted = Ecb::<Aes256, Pkcs7>::new_var(&key, &[])
+            .unwrap()
+            .encrypt_vec(base64::encode(&contents).as_bytes());
+        let ciphertext = base64::encode(encrypted);
+
+        for &chunk_size in &[1, 5, 64, 10_000] {
+            let got = decrypt_in_chunks(key, ciphertext.as_bytes(), chunk_size);
+
+            assert_eq!(got, contents, "Chunk size: {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn empty_input_gives_empty_output() {
+        let got = decrypt_in_chunks(DecryptionKey::generate(), b"", 1);
+
+        assert!(got.is_empty());
+    }
+
+    #[test]
+    fn the_wrong_key_is_detected() {
+        let key = DecryptionKey::generate();
+        let ciphertext = key.encrypt_base64(base64::encode(file_contents()));
+        let mut decryptor = StreamDecryptor::new(DecryptionKey::generate());
+        let mut output = Vec::new();
+
+        let got = decryptor
+            .feed(ciphertext.as_bytes(), &mut output)
+            .and_then(|_| decryptor.finish(&mut output));
+
+        assert!(got.is_err());
+    }
+}
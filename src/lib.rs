@@ -9,25 +9,43 @@ extern crate pretty_assertions;
 mod account;
 mod app;
 mod attachment;
+mod diff;
 pub mod endpoints;
+mod folder;
+mod generator;
 mod id;
 mod keys;
+mod matcher;
 mod parser;
+mod reference;
 mod session;
 mod share;
+mod template;
 mod vault;
 
 pub use account::Account;
 pub use attachment::Attachment;
+pub use diff::{Change, ModifiedAccount, VaultDiff};
+pub use folder::Folder;
+pub use generator::{GeneratorError, PassphraseGenerator, PasswordGenerator};
 pub use id::Id;
-pub use keys::{DecryptionError, DecryptionKey, LoginKey, PrivateKey};
+pub use keys::{
+    derive_keys, derive_keys_async, DecryptionError, DecryptionKey, LoginKey,
+    PrivateKey,
+};
+pub use matcher::{
+    EquivalentDomains, Match, MatchKind, NeverUrl, NeverUrlKind, UrlMatcher,
+    UrlRule,
+};
 pub use parser::VaultParseError;
+pub use reference::{Field, ParseReferenceError, Reference, ResolveError};
 pub use session::Session;
+pub use share::Share;
+pub use template::{RenderError, RenderErrorKind, Template, TemplateError};
 pub use vault::Vault;
 
 // these guys aren't fully completed yet
 pub(crate) use app::App;
-pub(crate) use share::Share;
 
 /// The default user agent to use when communicating with the LastPass server.
 pub const DEFAULT_USER_AGENT: &str =
@@ -0,0 +1,467 @@
+//! Figuring out which [`Account`]s apply to a particular website.
+
+use crate::{Account, Id};
+use url::Url;
+
+/// How closely an [`Account`]'s URL matches a website.
+///
+/// Variants are ordered from best to worst, so sorting by [`MatchKind`] puts
+/// the most relevant accounts first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchKind {
+    /// The host, port and path are all identical.
+    Exact,
+    /// The host and port are identical.
+    Host,
+    /// One host is a subdomain of the other, or the hosts are the same but
+    /// the ports differ.
+    Subdomain,
+    /// Both hosts belong to the same registrable domain (e.g.
+    /// `login.example.com` and `www.example.com`).
+    Domain,
+    /// The registrable domains are different, but the user has said they are
+    /// equivalent (e.g. `google.com` and `youtube.com`).
+    Equivalent,
+}
+
+/// An [`Account`] which applies to a website.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Match<'a> {
+    pub account: &'a Account,
+    pub kind: MatchKind,
+}
+
+/// A set of domains which should be treated as the same website.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct EquivalentDomains {
+    pub id: Id,
+    pub domains: Vec<String>,
+}
+
+impl EquivalentDomains {
+    pub fn new(id: Id, domains: Vec<String>) -> Self {
+        EquivalentDomains { id, domains }
+    }
+
+    fn contains(&self, domain: &str) -> bool {
+        self.domains.iter().any(|d| d.eq_ignore_ascii_case(domain))
+    }
+}
+
+/// A user-defined rule which tightens how URLs are matched for a particular
+/// website.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct UrlRule {
+    /// The host (and optional path prefix) this rule applies to, e.g.
+    /// `"example.com/accounts"`.
+    pub url: String,
+    /// Only match accounts with exactly the same host.
+    pub exact_host: bool,
+    /// Only match accounts with exactly the same port.
+    pub exact_port: bool,
+    /// Compare paths case-insensitively.
+    pub case_insensitive: bool,
+}
+
+impl UrlRule {
+    pub fn new(
+        url: String,
+        exact_host: bool,
+        exact_port: bool,
+        case_insensitive: bool,
+    ) -> Self {
+        UrlRule {
+            url,
+            exact_host,
+            exact_port,
+            case_insensitive,
+        }
+    }
+
+    /// Does this rule apply to the website?
+    fn applies_to(&self, site: &Url) -> bool {
+        pattern_matches(&self.url, site, self.case_insensitive)
+    }
+
+    fn path_matches(&self, path: &str) -> bool {
+        has_prefix(path, pattern_path(&self.url), self.case_insensitive)
+    }
+}
+
+/// Which LastPass feature a [`NeverUrl`] turns off.
+///
+/// LastPass doesn't document the codes used in `NEVR` chunks. This mapping
+/// follows the order the browser extension lists its "Never URLs" options in,
+/// and anything else is kept as [`NeverUrlKind::Unknown`] so the raw code
+/// isn't lost.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NeverUrlKind {
+    /// Never offer to save new accounts for this site.
+    NeverSave,
+    /// Never offer to generate passwords for this site.
+    NeverGenerate,
+    /// Never fill in forms (addresses, credit cards, etc.) on this site.
+    NeverAutofill,
+    /// Never automatically log in to this site.
+    NeverAutologin,
+    /// Never show LastPass icons in this site's input fields.
+    NeverShowIcons,
+    /// A code this crate doesn't recognise.
+    Unknown(String),
+}
+
+impl NeverUrlKind {
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "0" => NeverUrlKind::NeverSave,
+            "1" => NeverUrlKind::NeverGenerate,
+            "2" => NeverUrlKind::NeverAutofill,
+            "3" => NeverUrlKind::NeverAutologin,
+            "4" => NeverUrlKind::NeverShowIcons,
+            other => NeverUrlKind::Unknown(other.to_string()),
+        }
+    }
+
+    /// The raw code stored in the vault.
+    pub fn code(&self) -> &str {
+        match self {
+            NeverUrlKind::NeverSave => "0",
+            NeverUrlKind::NeverGenerate => "1",
+            NeverUrlKind::NeverAutofill => "2",
+            NeverUrlKind::NeverAutologin => "3",
+            NeverUrlKind::NeverShowIcons => "4",
+            NeverUrlKind::Unknown(code) => code,
+        }
+    }
+}
+
+/// A site the user has excluded from some LastPass feature (e.g. "never
+/// autologin"), as stored in the vault's `NEVR` chunks.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct NeverUrl {
+    pub kind: NeverUrlKind,
+    /// The host (and optional path prefix) being excluded, e.g.
+    /// `"drive.google.com"`.
+    pub url: String,
+}
+
+impl NeverUrl {
+    pub fn new(kind: NeverUrlKind, url: String) -> Self {
+        NeverUrl { kind, url }
+    }
+
+    /// Does this exclusion apply to the website (or one of its subdomains)?
+    pub fn applies_to(&self, site: &Url) -> bool {
+        pattern_matches(&self.url, site, false)
+    }
+}
+
+/// Finds the [`Account`]s which apply to a website, mimicking how the
+/// LastPass browser extension decides what to autofill.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct UrlMatcher<'a> {
+    equivalent_domains: &'a [EquivalentDomains],
+    url_rules: &'a [UrlRule],
+}
+
+impl<'a> UrlMatcher<'a> {
+    pub fn new() -> Self { UrlMatcher::default() }
+
+    /// Treat the domains in each group as the same website.
+    pub fn with_equivalent_domains(
+        self,
+        equivalent_domains: &'a [EquivalentDomains],
+    ) -> Self {
+        UrlMatcher {
+            equivalent_domains,
+            ..self
+        }
+    }
+
+    /// Apply the user's [`UrlRule`]s.
+    pub fn with_url_rules(self, url_rules: &'a [UrlRule]) -> Self {
+        UrlMatcher { url_rules, ..self }
+    }
+
+    /// Find all accounts which apply to `site`, best matches first.
+    pub fn matches<'v, I>(&self, site: &Url, accounts: I) -> Vec<Match<'v>>
+    where
+        I: IntoIterator<Item = &'v Account>,
+    {
+        let mut matches: Vec<_> = accounts
+            .into_iter()
+            .filter(|account| !account.is_group())
+            .filter_map(|account| {
+                self.classify(site, &account.url)
+                    .map(|kind| Match { account, kind })
+            })
+            .collect();
+
+        // a stable sort, so equally good matches keep their original order
+        matches.sort_by_key(|m| m.kind);
+
+        matches
+    }
+
+    /// Figure out how well a `candidate` URL matches the `site`, if at all.
+    pub fn classify(&self, site: &Url, candidate: &Url) -> Option<MatchKind> {
+        let site_host = site.host_str()?;
+        let candidate_host = candidate.host_str()?;
+        let same_host = site_host.eq_ignore_ascii_case(candidate_host);
+        let same_port =
+            site.port_or_known_default() == candidate.port_or_known_default();
+
+        let kind = if same_host && same_port {
+            if site.path() == candidate.path() {
+                MatchKind::Exact
+            } else {
+                MatchKind::Host
+            }
+        } else if same_host
+            || is_subdomain(site_host, candidate_host)
+            || is_subdomain(candidate_host, site_host)
+        {
+            MatchKind::Subdomain
+        } else {
+            let site_domain = registrable_domain(site_host)?;
+            let candidate_domain = registrable_domain(candidate_host)?;
+
+            if site_domain.eq_ignore_ascii_case(candidate_domain) {
+                MatchKind::Domain
+            } else if self.are_equivalent(site_domain, candidate_domain) {
+                MatchKind::Equivalent
+            } else {
+                return None;
+            }
+        };
+
+        if let Some(rule) = self.url_rules.iter().find(|r| r.applies_to(site)) {
+            if (rule.exact_host && !same_host)
+                || (rule.exact_port && !same_port)
+                || !rule.path_matches(candidate.path())
+            {
+                return None;
+            }
+        }
+
+        Some(kind)
+    }
+
+    fn are_equivalent(&self, first: &str, second: &str) -> bool {
+        self.equivalent_domains
+            .iter()
+            .any(|group| group.contains(first) && group.contains(second))
+    }
+}
+
+/// Does a pattern like `"example.com/accounts"` match the site's host (or one
+/// of its parents) and path?
+fn pattern_matches(pattern: &str, site: &Url, case_insensitive: bool) -> bool {
+    let host = match site.host_str() {
+        Some(host) => host,
+        None => return false,
+    };
+    let pattern_host = pattern_host(pattern);
+
+    (host.eq_ignore_ascii_case(pattern_host)
+        || is_subdomain(host, pattern_host))
+        && has_prefix(site.path(), pattern_path(pattern), case_insensitive)
+}
+
+fn strip_scheme(pattern: &str) -> &str {
+    match pattern.find("://") {
+        Some(index) => &pattern[index + 3..],
+        None => pattern,
+    }
+}
+
+fn pattern_host(pattern: &str) -> &str {
+    strip_scheme(pattern).split('/').next().unwrap_or_default()
+}
+
+fn pattern_path(pattern: &str) -> &str {
+    let pattern = strip_scheme(pattern);
+
+    match pattern.find('/') {
+        Some(slash) => &pattern[slash..],
+        None => "",
+    }
+}
+
+fn has_prefix(path: &str, prefix: &str, case_insensitive: bool) -> bool {
+    if case_insensitive {
+        path.to_lowercase().starts_with(&prefix.to_lowercase())
+    } else {
+        path.starts_with(prefix)
+    }
+}
+
+/// The part of a host name someone can register (e.g. `example.co.uk` for
+/// `login.example.co.uk`).
+fn registrable_domain(host: &str) -> Option<&str> { psl::domain_str(host) }
+
+/// Is `host` a subdomain of `parent`?
+fn is_subdomain(host: &str, parent: &str) -> bool {
+    host.len() > parent.len()
+        && host[..host.len() - parent.len()].ends_with('.')
+        && host[host.len() - parent.len()..].eq_ignore_ascii_case(parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(
+        matcher: &UrlMatcher<'_>,
+        site: &str,
+        candidate: &str,
+    ) -> Option<MatchKind> {
+        matcher.classify(
+            &Url::parse(site).unwrap(),
+            &Url::parse(candidate).unwrap(),
+        )
+    }
+
+    #[test]
+    fn default_matching_rules() {
+        let matcher = UrlMatcher::new();
+        let inputs = vec![
+            (
+                "https://example.com/login",
+                "https://example.com/login",
+                Some(MatchKind::Exact),
+            ),
+            (
+                "https://example.com/login",
+                "https://example.com/",
+                Some(MatchKind::Host),
+            ),
+            (
+                "https://example.com/",
+                "https://example.com:8443/",
+                Some(MatchKind::Subdomain),
+            ),
+            (
+                "https://login.example.com/",
+                "https://example.com/",
+                Some(MatchKind::Subdomain),
+            ),
+            (
+                "https://login.example.com/",
+                "https://www.example.com/",
+                Some(MatchKind::Domain),
+            ),
+            (
+                "https://a.example.co.uk/",
+                "https://b.example.co.uk/",
+                Some(MatchKind::Domain),
+            ),
+            // different people own these, despite sharing a suffix
+            ("https://example.co.uk/", "https://other.co.uk/", None),
+            ("https://example.com/", "https://google.com/", None),
+            ("https://example.com/", "http://sn", None),
+        ];
+
+        for (site, candidate, should_be) in inputs {
+            assert_eq!(
+                classify(&matcher, site, candidate),
+                should_be,
+                "{} vs {}",
+                site,
+                candidate
+            );
+        }
+    }
+
+    #[test]
+    fn equivalent_domains() {
+        let domains = vec![EquivalentDomains::new(
+            Id::from("1"),
+            vec![String::from("google.com"), String::from("youtube.com")],
+        )];
+        let matcher = UrlMatcher::new().with_equivalent_domains(&domains);
+
+        assert_eq!(
+            classify(
+                &matcher,
+                "https://www.youtube.com/",
+                "https://accounts.google.com/"
+            ),
+            Some(MatchKind::Equivalent)
+        );
+        assert_eq!(
+            classify(&matcher, "https://youtube.com/", "https://example.com/"),
+            None
+        );
+    }
+
+    #[test]
+    fn never_urls_apply_to_subdomains_and_paths() {
+        let never = NeverUrl::new(
+            NeverUrlKind::NeverAutologin,
+            String::from("example.com/admin"),
+        );
+
+        assert!(never.applies_to(
+            &Url::parse("https://example.com/admin/users").unwrap()
+        ));
+        assert!(never
+            .applies_to(&Url::parse("http://www.example.com/admin").unwrap()));
+        assert!(!never.applies_to(&Url::parse("https://example.com/").unwrap()));
+        assert!(!never
+            .applies_to(&Url::parse("https://notexample.com/admin").unwrap()));
+    }
+
+    #[test]
+    fn url_rules_tighten_matching() {
+        let rules = vec![
+            UrlRule::new(String::from("example.com"), true, true, false),
+            UrlRule::new(String::from("bank.com/Personal"), false, false, true),
+        ];
+        let matcher = UrlMatcher::new().with_url_rules(&rules);
+
+        assert_eq!(
+            classify(
+                &matcher,
+                "https://login.example.com/",
+                "https://www.example.com/"
+            ),
+            None
+        );
+        assert_eq!(
+            classify(
+                &matcher,
+                "https://example.com/",
+                "https://example.com:8443/"
+            ),
+            None
+        );
+        assert_eq!(
+            classify(
+                &matcher,
+                "https://example.com/a",
+                "https://example.com/b"
+            ),
+            Some(MatchKind::Host)
+        );
+        assert_eq!(
+            classify(
+                &matcher,
+                "https://bank.com/personal/login",
+                "https://www.bank.com/PERSONAL"
+            ),
+            Some(MatchKind::Subdomain)
+        );
+        assert_eq!(
+            classify(
+                &matcher,
+                "https://bank.com/personal/login",
+                "https://bank.com/business"
+            ),
+            None
+        );
+    }
+}
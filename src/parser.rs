@@ -1,6 +1,7 @@
 use crate::{
     keys::{DecryptionError, DecryptionKey, PrivateKey},
-    Account, App, Attachment, Share, Vault,
+    Account, App, Attachment, EquivalentDomains, Id, NeverUrl, NeverUrlKind,
+    Share, UrlRule, Vault,
 };
 use byteorder::{BigEndian, ByteOrder};
 use std::{
@@ -24,6 +25,12 @@ pub(crate) fn parse(
     let Parser {
         vault_version,
         accounts,
+        orphan_attachments,
+        equivalent_domains,
+        url_rules,
+        never_urls,
+        account_count,
+        shares,
         local,
         ..
     } = parser;
@@ -32,6 +39,12 @@ pub(crate) fn parse(
     Ok(Vault {
         version,
         accounts,
+        orphan_attachments,
+        equivalent_domains,
+        url_rules,
+        never_urls,
+        account_count,
+        shares,
         local,
     })
 }
@@ -76,11 +89,27 @@ pub enum VaultParseError {
 struct Parser {
     vault_version: Option<u64>,
     accounts: Vec<Account>,
+    orphan_attachments: Vec<Attachment>,
+    equivalent_domains: Vec<EquivalentDomains>,
+    url_rules: Vec<UrlRule>,
+    never_urls: Vec<NeverUrl>,
+    account_count: Option<usize>,
     shares: Vec<Share>,
+    /// The shared folder whose accounts we're reading, if any.
+    current_share: Option<CurrentShare>,
     app: Option<App>,
     local: bool,
 }
 
+/// Each `SHAR` chunk is followed by the accounts inside that shared folder.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CurrentShare {
+    /// An index into [`Parser::shares`].
+    Readable(usize),
+    /// We couldn't get the folder's key, so its accounts are skipped.
+    Unreadable,
+}
+
 impl Parser {
     fn new() -> Self { Parser::default() }
 
@@ -123,8 +152,17 @@ impl Parser {
             b"ACCT" => self.handle_account(chunk.data, decryption_key)?,
             b"ATTA" => self.handle_attachment(chunk.data)?,
             b"LOCL" => self.local = true,
-            b"SHAR" => self.handle_share(chunk.data, private_key)?,
+            b"SHAR" => {
+                self.handle_share(chunk.data, decryption_key, private_key)
+            },
             b"AACT" => self.handle_app(chunk.data, decryption_key)?,
+            b"EQDN" => self.handle_equivalent_domain(chunk.data)?,
+            b"URUL" => self.url_rules.push(parse_url_rule(chunk.data)?),
+            b"NEVR" => self.never_urls.push(parse_never_url(chunk.data)?),
+            // the number of accounts in the vault
+            b"NMAC" => {
+                self.account_count = chunk.data_as_str()?.parse().ok();
+            },
             _ => {},
         }
 
@@ -136,7 +174,29 @@ impl Parser {
         buffer: &[u8],
         decryption_key: &DecryptionKey,
     ) -> Result<(), VaultParseError> {
-        self.accounts.push(parse_account(buffer, decryption_key)?);
+        let account = match self.current_share {
+            None => parse_account(buffer, decryption_key)?,
+            Some(CurrentShare::Readable(index)) => {
+                let share = &self.shares[index];
+                let mut account = parse_account(buffer, &share.key)?;
+
+                // the share's name acts as the folder's root group
+                account.group = if account.group.is_empty() {
+                    share.name.clone()
+                } else {
+                    format!("{}\\{}", share.name, account.group)
+                };
+                account.share = Some(share.id.clone());
+
+                account
+            },
+            Some(CurrentShare::Unreadable) => {
+                log::debug!("Skipping an account in an unreadable share");
+                return Ok(());
+            },
+        };
+
+        self.accounts.push(account);
 
         Ok(())
     }
@@ -155,23 +215,58 @@ impl Parser {
             Some(parent) => {
                 parent.attachments.push(attachment);
             },
-            None => unimplemented!(),
+            None => {
+                log::debug!(
+                    "Unable to find the parent of attachment {:?} ({:?})",
+                    attachment.id,
+                    attachment.parent
+                );
+                self.orphan_attachments.push(attachment);
+            },
         }
 
         Ok(())
     }
 
-    fn handle_share(
+    fn handle_equivalent_domain(
         &mut self,
         buffer: &[u8],
-        private_key: &PrivateKey,
     ) -> Result<(), VaultParseError> {
-        let share = parse_share(buffer, private_key)?;
-        self.shares.push(share);
+        // each EQDN chunk contains a single domain and the ID of its group
+        let (id, domain) = parse_equivalent_domain(buffer)?;
+
+        match self.equivalent_domains.iter_mut().find(|eq| eq.id == id) {
+            Some(group) => group.domains.push(domain),
+            None => self
+                .equivalent_domains
+                .push(EquivalentDomains::new(id, vec![domain])),
+        }
 
         Ok(())
     }
 
+    /// Shared folders which can't be read (e.g. because we don't have the
+    /// user's private key) are skipped instead of making the whole vault
+    /// unusable.
+    fn handle_share(
+        &mut self,
+        buffer: &[u8],
+        decryption_key: &DecryptionKey,
+        private_key: &PrivateKey,
+    ) {
+        match parse_share(buffer, decryption_key, private_key) {
+            Ok(share) => {
+                self.shares.push(share);
+                self.current_share =
+                    Some(CurrentShare::Readable(self.shares.len() - 1));
+            },
+            Err(e) => {
+                log::warn!("Skipping a shared folder we can't read: {}", e);
+                self.current_share = Some(CurrentShare::Unreadable);
+            },
+        }
+    }
+
     fn handle_app(
         &mut self,
         buffer: &[u8],
@@ -223,19 +318,76 @@ pub(crate) fn parse_app(
     })
 }
 
+pub(crate) fn parse_equivalent_domain(
+    buffer: &[u8],
+) -> Result<(Id, String), VaultParseError> {
+    let (id, buffer) = read_parsed(buffer, "eqdn.id")?;
+    let (domain, buffer) = read_hex_string(buffer, "eqdn.domain")?;
+
+    let _ = buffer;
+
+    Ok((id, domain))
+}
+
+pub(crate) fn parse_url_rule(
+    buffer: &[u8],
+) -> Result<UrlRule, VaultParseError> {
+    let (url, buffer) = read_hex_string(buffer, "urul.url")?;
+    let (exact_host, buffer) = read_bool(buffer, "urul.exacthost")?;
+    let (exact_port, buffer) = read_bool(buffer, "urul.exactport")?;
+    let (case_insensitive, buffer) = read_bool(buffer, "urul.caseinsensitive")?;
+
+    let _ = buffer;
+
+    Ok(UrlRule::new(url, exact_host, exact_port, case_insensitive))
+}
+
+pub(crate) fn parse_never_url(
+    buffer: &[u8],
+) -> Result<NeverUrl, VaultParseError> {
+    let (code, buffer) = read_str_item(buffer, "nevr.code")?;
+    let (url, buffer) = read_hex_string(buffer, "nevr.url")?;
+
+    let _ = buffer;
+
+    Ok(NeverUrl::new(NeverUrlKind::from_code(code), url))
+}
+
 pub(crate) fn parse_share(
     buffer: &[u8],
-    _private_key: &PrivateKey,
+    decryption_key: &DecryptionKey,
+    private_key: &PrivateKey,
 ) -> Result<Share, VaultParseError> {
-    // let (id, buffer) = read_parsed(buffer, "share.id")?;
-    // let (key, buffer) = read_hex(buffer, "share")?;
-    //
-    // let _ = buffer;
-
-    unimplemented!(
-        "TODO: Implement this when I share a password with someone.\n\nBuffer: {:?}",
-        buffer,
-    )
+    let (id, buffer) = read_parsed(buffer, "share.id")?;
+    let (encrypted_key, buffer) = read_hex(buffer, "share.key")?;
+    let (encrypted_name, buffer) = read_str_item(buffer, "share.name")?;
+    let (readonly, buffer) = read_bool(buffer, "share.readonly")?;
+
+    let _ = buffer;
+
+    // the folder's key is hex-encoded then encrypted with the user's
+    // public key
+    let key = private_key
+        .decrypt(decryption_key, &encrypted_key)
+        .and_then(|hex| Ok(DecryptionKey::from_hex(hex)?))
+        .map_err(|e| VaultParseError::UnableToDecrypt {
+            field: "share.key",
+            inner: e,
+        })?;
+    let name = key
+        .decrypt_base64(encrypted_name)
+        .and_then(|name| Ok(String::from_utf8(name)?))
+        .map_err(|e| VaultParseError::UnableToDecrypt {
+            field: "share.name",
+            inner: e,
+        })?;
+
+    Ok(Share {
+        id,
+        name,
+        key,
+        readonly,
+    })
 }
 
 pub(crate) fn parse_attachment(
@@ -272,7 +424,7 @@ pub(crate) fn parse_account(
         read_encrypted(buffer, "account.group", decryption_key)?;
     let (url, buffer) = read_hex_string(buffer, "account.url")?;
     let (note, buffer) =
-        read_encrypted(buffer, "account.note", &decryption_key)?;
+        read_encrypted(buffer, "account.note", decryption_key)?;
     let (fav, buffer) = read_bool(buffer, "account.fav")?;
     let buffer = skip(buffer, "account.sharedfromaid")?;
     let (username, buffer) =
@@ -331,6 +483,7 @@ pub(crate) fn parse_account(
             inner: Box::new(e),
         })?,
         attachments: Vec::new(),
+        share: None,
     })
 }
 
@@ -584,6 +737,269 @@ mod tests {
         assert_eq!(parser.vault_version, Some(198));
     }
 
+    fn write_chunk(buffer: &mut Vec<u8>, name: &[u8], data: &[u8]) {
+        buffer.write_all(name).unwrap();
+        buffer.write_u32::<BigEndian>(data.len() as u32).unwrap();
+        buffer.write_all(data).unwrap();
+    }
+
+    fn items(items: &[&[u8]]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+
+        for item in items {
+            buffer.write_u32::<BigEndian>(item.len() as u32).unwrap();
+            buffer.write_all(item).unwrap();
+        }
+
+        buffer
+    }
+
+    fn account_chunk(id: &str, name: &str, key: &DecryptionKey) -> Vec<u8> {
+        let name = key.encrypt(name.as_bytes());
+        let url = hex::encode("http://sn");
+
+        let mut fields: Vec<&[u8]> = vec![
+            id.as_bytes(),  // id
+            &name,          // name
+            b"",            // group
+            url.as_bytes(), // url
+            b"",            // note
+            b"0",           // fav
+            b"",            // sharedfromaid
+            b"",            // username
+            b"",            // password
+            b"0",           // pwprotect
+            b"",            // genpw
+            b"",            // sn
+            b"0",           // last_touch
+        ];
+        // autologin through to deleted
+        fields.extend_from_slice(&[&b""[..]; 13]);
+        fields.extend_from_slice(&[
+            b"",  // attachkey_encrypted
+            b"1", // attachpresent
+            b"",  // individualshare
+            b"",  // notetype
+            b"",  // noalert
+            b"0", // last_modified_gmt
+            b"",  // hasbeenshared
+            b"",  // last_pwchange_gmt
+            b"",  // created_gmt
+            b"",  // vulnerable
+        ]);
+
+        items(&fields)
+    }
+
+    fn attachment_chunk(id: &str, parent: &str) -> Vec<u8> {
+        items(&[
+            id.as_bytes(),
+            parent.as_bytes(),
+            b"other:txt",
+            b"100000027282",
+            b"42",
+            b"",
+        ])
+    }
+
+    /// A synthetic `SHAR` chunk, encrypted the same way as the real thing.
+    fn share_chunk(id: &str, name: &str, key: &DecryptionKey) -> Vec<u8> {
+        let encrypted_key =
+            crate::keys::tests::encrypt(hex::encode(key).as_bytes());
+        let encrypted_key = hex::encode(encrypted_key);
+        let name = key.encrypt_base64(name);
+
+        items(&[
+            id.as_bytes(),
+            encrypted_key.as_bytes(),
+            name.as_bytes(),
+            b"0", // readonly
+        ])
+    }
+
+    #[test]
+    fn accounts_after_a_share_use_its_key() {
+        let decryption_key = DecryptionKey::generate();
+        let private_key = crate::keys::tests::private_key(&decryption_key);
+        let share_key = DecryptionKey::generate();
+        let mut buffer = Vec::new();
+        write_chunk(&mut buffer, b"LPAV", b"198");
+        write_chunk(
+            &mut buffer,
+            b"ACCT",
+            &account_chunk("1", "Mine", &decryption_key),
+        );
+        write_chunk(
+            &mut buffer,
+            b"SHAR",
+            &share_chunk("42", "Shared-Team", &share_key),
+        );
+        write_chunk(
+            &mut buffer,
+            b"ACCT",
+            &account_chunk("2", "Theirs", &share_key),
+        );
+
+        let got = parse(&buffer, &decryption_key, &private_key).unwrap();
+
+        assert_eq!(got.shares.len(), 1);
+        assert_eq!(got.shares[0].id, Id::from("42"));
+        assert_eq!(got.shares[0].name, "Shared-Team");
+        assert_eq!(got.shares[0].key, share_key);
+        assert!(!got.shares[0].readonly);
+        assert_eq!(got.accounts[0].path(), "Mine");
+        assert_eq!(got.accounts[0].share, None);
+        assert_eq!(got.accounts[1].path(), "Shared-Team/Theirs");
+        assert_eq!(got.accounts[1].share, Some(Id::from("42")));
+    }
+
+    #[test]
+    fn unreadable_shares_are_skipped() {
+        // without the private key we can't get the share's key
+        let (decryption_key, private_key) = keys();
+        let share_key = DecryptionKey::generate();
+        let mut buffer = Vec::new();
+        write_chunk(&mut buffer, b"LPAV", b"198");
+        write_chunk(
+            &mut buffer,
+            b"ACCT",
+            &account_chunk("1", "Mine", &decryption_key),
+        );
+        write_chunk(
+            &mut buffer,
+            b"SHAR",
+            &share_chunk("42", "Shared-Team", &share_key),
+        );
+        write_chunk(
+            &mut buffer,
+            b"ACCT",
+            &account_chunk("2", "Theirs", &share_key),
+        );
+
+        let got = parse(&buffer, &decryption_key, &private_key).unwrap();
+
+        assert!(got.shares.is_empty());
+        assert_eq!(got.accounts.len(), 1);
+        assert_eq!(got.accounts[0].name, "Mine");
+    }
+
+    #[test]
+    fn attachments_without_a_known_parent_are_orphans() {
+        let (decryption_key, private_key) = keys();
+        let mut buffer = Vec::new();
+        write_chunk(&mut buffer, b"LPAV", b"1");
+        write_chunk(
+            &mut buffer,
+            b"ACCT",
+            &account_chunk("1234", "My Note", &decryption_key),
+        );
+        write_chunk(&mut buffer, b"ATTA", &attachment_chunk("1234-1", "1234"));
+        write_chunk(&mut buffer, b"ATTA", &attachment_chunk("5678-1", "5678"));
+
+        let got = parse(&buffer, &decryption_key, &private_key).unwrap();
+
+        assert_eq!(got.accounts.len(), 1);
+        assert_eq!(got.accounts[0].name, "My Note");
+        assert_eq!(got.accounts[0].attachments.len(), 1);
+        assert_eq!(got.accounts[0].attachments[0].id, Id::from("1234-1"));
+        assert_eq!(got.orphan_attachments.len(), 1);
+        assert_eq!(got.orphan_attachments[0].id, Id::from("5678-1"));
+        assert_eq!(got.orphan_attachments[0].parent, Id::from("5678"));
+    }
+
+    #[test]
+    fn parse_equivalent_domains_and_url_rules() {
+        let (decryption_key, private_key) = keys();
+        let google = hex::encode("google.com");
+        let youtube = hex::encode("youtube.com");
+        let rule = hex::encode("example.com/login");
+        let mut buffer = Vec::new();
+        write_chunk(&mut buffer, b"LPAV", b"1");
+        write_chunk(&mut buffer, b"EQDN", &items(&[b"1", google.as_bytes()]));
+        write_chunk(&mut buffer, b"EQDN", &items(&[b"1", youtube.as_bytes()]));
+        write_chunk(&mut buffer, b"EQDN", &items(&[b"2", google.as_bytes()]));
+        write_chunk(
+            &mut buffer,
+            b"URUL",
+            &items(&[rule.as_bytes(), b"1", b"0", b"1"]),
+        );
+
+        let got = parse(&buffer, &decryption_key, &private_key).unwrap();
+
+        assert_eq!(
+            got.equivalent_domains,
+            vec![
+                EquivalentDomains::new(
+                    Id::from("1"),
+                    vec![
+                        String::from("google.com"),
+                        String::from("youtube.com")
+                    ]
+                ),
+                EquivalentDomains::new(
+                    Id::from("2"),
+                    vec![String::from("google.com")]
+                ),
+            ]
+        );
+        assert_eq!(
+            got.url_rules,
+            vec![UrlRule::new(
+                String::from("example.com/login"),
+                true,
+                false,
+                true
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_never_urls_and_the_account_count() {
+        let (decryption_key, private_key) = keys();
+        let google = hex::encode("drive.google.com");
+        let example = hex::encode("example.com/admin");
+        let mut buffer = Vec::new();
+        write_chunk(&mut buffer, b"LPAV", b"1");
+        write_chunk(&mut buffer, b"NMAC", b"42");
+        write_chunk(&mut buffer, b"NEVR", &items(&[b"0", google.as_bytes()]));
+        write_chunk(&mut buffer, b"NEVR", &items(&[b"4", google.as_bytes()]));
+        write_chunk(&mut buffer, b"NEVR", &items(&[b"9", example.as_bytes()]));
+
+        let got = parse(&buffer, &decryption_key, &private_key).unwrap();
+
+        assert_eq!(got.account_count, Some(42));
+        assert_eq!(
+            got.never_urls,
+            vec![
+                NeverUrl::new(
+                    NeverUrlKind::NeverSave,
+                    String::from("drive.google.com")
+                ),
+                NeverUrl::new(
+                    NeverUrlKind::NeverShowIcons,
+                    String::from("drive.google.com")
+                ),
+                NeverUrl::new(
+                    NeverUrlKind::Unknown(String::from("9")),
+                    String::from("example.com/admin")
+                ),
+            ]
+        );
+        assert_eq!(got.never_urls[2].kind.code(), "9");
+    }
+
+    #[test]
+    fn a_garbled_account_count_is_ignored() {
+        let (decryption_key, private_key) = keys();
+        let mut buffer = Vec::new();
+        write_chunk(&mut buffer, b"LPAV", b"1");
+        write_chunk(&mut buffer, b"NMAC", b"lots");
+
+        let got = parse(&buffer, &decryption_key, &private_key).unwrap();
+
+        assert_eq!(got.account_count, None);
+    }
+
     #[test]
     fn read_the_dummy_vault() {
         let raw = include_bytes!("vault_from_dummy_account.bin");
@@ -607,6 +1023,7 @@ mod tests {
                     last_touch: String::from("1586688785"),
                     last_modified: String::from("1586717585"),
                     attachments: Vec::new(),
+                    share: None,
                 },
                 Account {
                     id: Id::from("8852885818375729232"),
@@ -624,6 +1041,7 @@ mod tests {
                     last_touch: String::from("0"),
                     last_modified: String::from("1586717558"),
                     attachments: Vec::new(),
+                    share: None,
                 },
                 Account {
                     id: Id::from("8994685833508535250"),
@@ -641,6 +1059,7 @@ mod tests {
                     last_touch: String::from("0"),
                     last_modified: String::from("1586717569"),
                     attachments: Vec::new(),
+                    share: None,
                 },
                 Account {
                     id: Id::from("7483661148987913660"),
@@ -658,6 +1077,7 @@ mod tests {
                     last_touch: String::from("0"),
                     last_modified: String::from("1586717578"),
                     attachments: Vec::new(),
+                    share: None,
                 },
                 Account {
                     id: Id::from("5211400216940069976"),
@@ -675,6 +1095,7 @@ mod tests {
                     last_touch: String::from("0"),
                     last_modified: String::from("1586717700"),
                     attachments: Vec::new(),
+                    share: None,
                 },
                 Account {
                     id: Id::from("533903346832032070"),
@@ -701,13 +1122,43 @@ mod tests {
                             encrypted_filename: String::from("!zdLMAcQ9okxR3MFWNjoCaw==|B7NqfcNPX0IayFXNtxkqEw=="),
                         },
                     ],
+                    share: None,
                 },
-            ]
+            ],
+            orphan_attachments: Vec::new(),
+            equivalent_domains: Vec::new(),
+            url_rules: Vec::new(),
+            never_urls: vec![NeverUrl::new(
+                NeverUrlKind::NeverAutologin,
+                String::from("drive.google.com"),
+            )],
+            account_count: Some(6),
+            shares: Vec::new(),
         };
         let (decryption_key, private_key) = keys();
 
-        let got = parse(raw, &decryption_key, &private_key).unwrap();
+        let mut got = parse(raw, &decryption_key, &private_key).unwrap();
 
+        // LastPass gives everyone the same (rather long) list of equivalent
+        // domains and URL rules, so just spot-check them
+        let equivalent_domains = std::mem::take(&mut got.equivalent_domains);
+        let url_rules = std::mem::take(&mut got.url_rules);
         assert_eq!(got, should_be);
+        assert_eq!(equivalent_domains.len(), 69);
+        assert!(equivalent_domains.contains(&EquivalentDomains::new(
+            Id::from("4"),
+            vec![
+                String::from("youtube.com"),
+                String::from("google.com"),
+                String::from("gmail.com")
+            ]
+        )));
+        assert_eq!(url_rules.len(), 27);
+        assert!(url_rules.contains(&UrlRule::new(
+            String::from("logmein.com"),
+            true,
+            false,
+            false
+        )));
     }
 }
@@ -0,0 +1,333 @@
+use crate::{Account, Vault};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+/// A reference to one of an [`Account`]'s fields, written as
+/// `"path:field"` (e.g. `"prod/db:password"` or `"Servers/web:Hostname"`).
+///
+/// The account may be referred to by its [`crate::Id`], its full path or its
+/// name, and the field defaults to the password when it is left out. Any
+/// field which isn't one of the standard ones is looked up in the secure
+/// note's [`Account::note_fields()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// The account's [`crate::Id`], path or name.
+    pub account: String,
+    pub field: Field,
+}
+
+impl Reference {
+    pub fn new<S: Into<String>>(account: S, field: Field) -> Self {
+        Reference {
+            account: account.into(),
+            field,
+        }
+    }
+
+    /// Find the [`Account`] this [`Reference`] points to.
+    pub fn account<'v>(
+        &self,
+        vault: &'v Vault,
+    ) -> Result<&'v Account, ResolveError> {
+        match self.candidates(vault).as_slice() {
+            [account] => Ok(account),
+            [] => Err(ResolveError::NotFound {
+                account: self.account.clone(),
+            }),
+            many => Err(ResolveError::Ambiguous {
+                account: self.account.clone(),
+                matches: many.len(),
+            }),
+        }
+    }
+
+    /// Every [`Account`] this [`Reference`] could point to, trying the
+    /// [`crate::Id`] first, then the path, then the name.
+    ///
+    /// [`Reference::account()`] only succeeds when there is exactly one.
+    pub fn candidates<'v>(&self, vault: &'v Vault) -> Vec<&'v Account> {
+        if let Some(account) =
+            vault.get_account_by_id(&self.account.as_str().into())
+        {
+            return vec![account];
+        }
+
+        let mut candidates: Vec<_> =
+            vault.find_by_path(&self.account).collect();
+        if candidates.is_empty() {
+            candidates = vault.find_by_name(&self.account).collect();
+        }
+        candidates.retain(|account| !account.is_group());
+
+        candidates
+    }
+
+    /// Look up the value this [`Reference`] points to.
+    pub fn resolve<'v>(
+        &self,
+        vault: &'v Vault,
+    ) -> Result<&'v str, ResolveError> {
+        let account = self.account(vault)?;
+
+        self.field
+            .get(account)
+            .ok_or_else(|| ResolveError::UnknownField {
+                account: self.account.clone(),
+                field: self.field.to_string(),
+            })
+    }
+}
+
+impl FromStr for Reference {
+    type Err = ParseReferenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (account, field) = match s.rfind(':') {
+            Some(colon) => (&s[..colon], s[colon + 1..].parse()?),
+            None => (s, Field::Password),
+        };
+
+        if account.is_empty() {
+            return Err(ParseReferenceError::MissingAccount);
+        }
+
+        Ok(Reference::new(account, field))
+    }
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.account, self.field)
+    }
+}
+
+/// The part of an [`Account`] a [`Reference`] points to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Id,
+    Name,
+    Username,
+    Password,
+    Url,
+    Notes,
+    /// A secure note's field (see [`Account::note_field()`]).
+    NoteField(String),
+}
+
+impl Field {
+    /// Get this field's value from an [`Account`].
+    ///
+    /// Secure notes keep their usernames and passwords (e.g. for a database)
+    /// in their note fields, so those are checked when the account's own
+    /// field is empty.
+    pub fn get<'a>(&self, account: &'a Account) -> Option<&'a str> {
+        match self {
+            Field::Id => Some(&account.id),
+            Field::Name => Some(&account.name),
+            Field::Username => non_empty(&account.username)
+                .or_else(|| account.note_field("Username")),
+            Field::Password => non_empty(&account.password)
+                .or_else(|| account.note_field("Password")),
+            Field::Url => Some(account.url.as_str()),
+            Field::Notes if account.note_fields().is_empty() => {
+                Some(&account.note)
+            },
+            Field::Notes => Some(account.note_field("Notes").unwrap_or("")),
+            Field::NoteField(name) => account.note_field(name),
+        }
+    }
+}
+
+fn non_empty(s: &str) -> Option<&str> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+impl FromStr for Field {
+    type Err = ParseReferenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let field = match s.to_lowercase().as_str() {
+            "" => return Err(ParseReferenceError::MissingField),
+            "id" => Field::Id,
+            "name" => Field::Name,
+            "username" => Field::Username,
+            "password" => Field::Password,
+            "url" => Field::Url,
+            "notes" => Field::Notes,
+            _ => Field::NoteField(s.to_string()),
+        };
+
+        Ok(field)
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Id => write!(f, "id"),
+            Field::Name => write!(f, "name"),
+            Field::Username => write!(f, "username"),
+            Field::Password => write!(f, "password"),
+            Field::Url => write!(f, "url"),
+            Field::Notes => write!(f, "notes"),
+            Field::NoteField(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// The ways a [`Reference`] can be malformed.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ParseReferenceError {
+    #[error("The reference doesn't say which account to use")]
+    MissingAccount,
+    #[error("The reference has a \":\" but no field name")]
+    MissingField,
+}
+
+/// Reasons a [`Reference`] couldn't be resolved.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ResolveError {
+    #[error("Could not find \"{}\"", account)]
+    NotFound { account: String },
+    #[error("\"{}\" matches {} accounts", account, matches)]
+    Ambiguous { account: String, matches: usize },
+    #[error("\"{}\" doesn't have a \"{}\" field", account, field)]
+    UnknownField { account: String, field: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::tests::dummy_vault;
+
+    #[test]
+    fn parse_references() {
+        let inputs = vec![
+            (
+                "prod/db:password",
+                Reference::new("prod/db", Field::Password),
+            ),
+            ("prod/db", Reference::new("prod/db", Field::Password)),
+            (
+                "prod/db:USERNAME",
+                Reference::new("prod/db", Field::Username),
+            ),
+            (
+                "Servers/web:Hostname",
+                Reference::new(
+                    "Servers/web",
+                    Field::NoteField("Hostname".into()),
+                ),
+            ),
+            (
+                "http://example.com:id",
+                Reference::new("http://example.com", Field::Id),
+            ),
+        ];
+
+        for (src, should_be) in inputs {
+            let got: Reference = src.parse().unwrap();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn invalid_references() {
+        assert_eq!(
+            "".parse::<Reference>(),
+            Err(ParseReferenceError::MissingAccount)
+        );
+        assert_eq!(
+            ":password".parse::<Reference>(),
+            Err(ParseReferenceError::MissingAccount)
+        );
+        assert_eq!(
+            "prod/db:".parse::<Reference>(),
+            Err(ParseReferenceError::MissingField)
+        );
+    }
+
+    #[test]
+    fn references_round_trip() {
+        for src in &["prod/db:password", "Servers/web:Hostname"] {
+            let reference: Reference = src.parse().unwrap();
+            assert_eq!(reference.to_string(), *src);
+        }
+    }
+
+    #[test]
+    fn resolve_against_the_vault() {
+        let vault = dummy_vault();
+        let account = vault.find_by_name("Another Password").next().unwrap();
+
+        let got = Reference::new("Another Password", Field::Password)
+            .resolve(&vault)
+            .unwrap();
+        assert_eq!(got, account.password);
+
+        let got = Reference::new("8852885818375729232", Field::Username)
+            .resolve(&vault)
+            .unwrap();
+        assert_eq!(got, account.username);
+
+        let got = Reference::new("Some Folder/My Address", Field::Name)
+            .resolve(&vault)
+            .unwrap();
+        assert_eq!(got, "My Address");
+    }
+
+    #[test]
+    fn secure_note_fields() {
+        let mut vault = dummy_vault();
+        let mut server = Account::new_secure_note("db", "Database");
+        server.move_to("prod");
+        server.note = String::from(
+            "NoteType:Database\nHostname:db.example.com\nUsername:admin\nPassword:hunter2\nNotes:primary",
+        );
+        vault.accounts.push(server);
+
+        let resolve =
+            |src: &str| src.parse::<Reference>().unwrap().resolve(&vault);
+
+        assert_eq!(resolve("prod/db"), Ok("hunter2"));
+        assert_eq!(resolve("prod/db:username"), Ok("admin"));
+        assert_eq!(resolve("prod/db:hostname"), Ok("db.example.com"));
+        assert_eq!(resolve("prod/db:notes"), Ok("primary"));
+        assert_eq!(
+            resolve("prod/db:Port"),
+            Err(ResolveError::UnknownField {
+                account: String::from("prod/db"),
+                field: String::from("Port"),
+            })
+        );
+    }
+
+    #[test]
+    fn missing_and_ambiguous_accounts() {
+        let mut vault = dummy_vault();
+        let mut duplicate = vault.accounts[1].clone();
+        duplicate.id = "1234".into();
+        let name = duplicate.name.clone();
+        vault.accounts.push(duplicate);
+
+        assert_eq!(
+            Reference::new("Missing", Field::Password).resolve(&vault),
+            Err(ResolveError::NotFound {
+                account: String::from("Missing")
+            })
+        );
+        assert_eq!(
+            Reference::new(name.as_str(), Field::Password).resolve(&vault),
+            Err(ResolveError::Ambiguous {
+                account: name,
+                matches: 2
+            })
+        );
+    }
+}
@@ -12,4 +12,7 @@ pub struct Session {
     pub private_key: PrivateKey,
     /// The PHP session ID.
     pub session_id: String,
+    /// The number of iterations used when calculating the [`crate::LoginKey`]
+    /// and [`crate::DecryptionKey`] for this session.
+    pub iterations: usize,
 }
@@ -1,10 +1,48 @@
-use crate::Id;
+use crate::{DecryptionKey, Id};
 
+/// A shared folder.
+///
+/// The accounts inside a shared folder are encrypted using the folder's own
+/// key instead of the user's [`DecryptionKey`], so everyone it is shared with
+/// can read them.
 #[derive(Debug, Clone, PartialEq)]
 #[non_exhaustive]
-pub(crate) struct Share {
+pub struct Share {
     pub id: Id,
+    /// The folder's name (e.g. `"Shared-Team"`), which is also the first group
+    /// in the path of every account inside it.
     pub name: String,
-    pub key: Vec<u8>,
+    /// The key used to encrypt the folder's accounts.
+    pub key: DecryptionKey,
+    /// Is the user only allowed to read the folder's accounts?
     pub readonly: bool,
 }
+
+impl Share {
+    pub fn new(
+        id: Id,
+        name: String,
+        key: DecryptionKey,
+        readonly: bool,
+    ) -> Self {
+        Share {
+            id,
+            name,
+            key,
+            readonly,
+        }
+    }
+
+    /// An account's group relative to this shared folder (e.g. `"Servers"`
+    /// for `"Shared-Team\\Servers"`), which is how the server stores it.
+    pub fn relative_group<'g>(&self, group: &'g str) -> &'g str {
+        if group == self.name {
+            return "";
+        }
+
+        group
+            .strip_prefix(self.name.as_str())
+            .and_then(|rest| rest.strip_prefix('\\'))
+            .unwrap_or(group)
+    }
+}
@@ -0,0 +1,496 @@
+use crate::{
+    Account, Attachment, DecryptionError, DecryptionKey, Field, Id,
+    ParseReferenceError, Reference, ResolveError, Vault,
+};
+use std::{collections::HashMap, str::FromStr};
+
+/// A text file (e.g. an app's config) containing references to secrets in
+/// the [`Vault`].
+///
+/// Secrets are written as `{{ lastpass "prod/db" "password" }}`, where the
+/// field is optional and defaults to the password (see [`Reference`]), and an
+/// attachment's contents can be inserted using
+/// `{{ lastpass_attachment "prod/tls" "cert.pem" }}`. Write `\{{` for a
+/// literal `{{`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field {
+        line: usize,
+        reference: Reference,
+    },
+    Attachment {
+        line: usize,
+        account: String,
+        filename: String,
+    },
+}
+
+impl Template {
+    /// The [`Reference`]s used by this [`Template`].
+    pub fn references(&self) -> impl Iterator<Item = &Reference> + '_ {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Field { reference, .. } => Some(reference),
+            _ => None,
+        })
+    }
+
+    /// Find the attachments this [`Template`] needs, so they can be
+    /// downloaded and passed to [`Template::render()`].
+    pub fn attachments<'v>(
+        &self,
+        vault: &'v Vault,
+        decryption_key: &DecryptionKey,
+    ) -> Result<Vec<&'v Attachment>, RenderError> {
+        let mut attachments: Vec<&Attachment> = Vec::new();
+
+        for part in &self.parts {
+            if let Part::Attachment {
+                line,
+                account,
+                filename,
+            } = part
+            {
+                let attachment =
+                    find_attachment(vault, decryption_key, account, filename)
+                        .map_err(|kind| RenderError { line: *line, kind })?;
+
+                if !attachments.iter().any(|a| a.id == attachment.id) {
+                    attachments.push(attachment);
+                }
+            }
+        }
+
+        Ok(attachments)
+    }
+
+    /// Fill in the [`Template`], using the `attachments` (decrypted contents
+    /// keyed by [`Id`]) that were downloaded for
+    /// [`Template::attachments()`].
+    pub fn render(
+        &self,
+        vault: &Vault,
+        decryption_key: &DecryptionKey,
+        attachments: &HashMap<Id, Vec<u8>>,
+    ) -> Result<String, RenderError> {
+        let mut rendered = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Field { line, reference } => {
+                    let value =
+                        reference.resolve(vault).map_err(|e| RenderError {
+                            line: *line,
+                            kind: e.into(),
+                        })?;
+                    rendered.push_str(value);
+                },
+                Part::Attachment {
+                    line,
+                    account,
+                    filename,
+                } => {
+                    let value = attachment_contents(
+                        vault,
+                        decryption_key,
+                        attachments,
+                        account,
+                        filename,
+                    )
+                    .map_err(|kind| RenderError { line: *line, kind })?;
+                    rendered.push_str(value);
+                },
+            }
+        }
+
+        Ok(rendered)
+    }
+}
+
+fn find_attachment<'v>(
+    vault: &'v Vault,
+    decryption_key: &DecryptionKey,
+    account: &str,
+    filename: &str,
+) -> Result<&'v Attachment, RenderErrorKind> {
+    let owner: &Account = Reference::new(account, Field::Id).account(vault)?;
+    let attachment_key = owner.attachment_key(decryption_key)?;
+
+    for attachment in &owner.attachments {
+        if attachment.filename(&attachment_key)? == filename {
+            return Ok(attachment);
+        }
+    }
+
+    Err(RenderErrorKind::UnknownAttachment {
+        account: account.to_string(),
+        filename: filename.to_string(),
+    })
+}
+
+fn attachment_contents<'a>(
+    vault: &Vault,
+    decryption_key: &DecryptionKey,
+    attachments: &'a HashMap<Id, Vec<u8>>,
+    account: &str,
+    filename: &str,
+) -> Result<&'a str, RenderErrorKind> {
+    let attachment = find_attachment(vault, decryption_key, account, filename)?;
+    let contents = attachments.get(&attachment.id).ok_or_else(|| {
+        RenderErrorKind::AttachmentNotLoaded {
+            filename: filename.to_string(),
+        }
+    })?;
+
+    std::str::from_utf8(contents).map_err(|_| {
+        RenderErrorKind::BinaryAttachment {
+            filename: filename.to_string(),
+        }
+    })
+}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = src;
+        let mut line = 1;
+
+        while let Some(start) = rest.find("{{") {
+            let (before, tag) = rest.split_at(start);
+            line += before.matches('\n').count();
+
+            // "\{{" is a literal "{{"
+            if let Some(before) = before.strip_suffix('\\') {
+                text.push_str(before);
+                text.push_str("{{");
+                rest = &tag[2..];
+                continue;
+            }
+
+            text.push_str(before);
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+
+            let end = tag.find("}}").ok_or(TemplateError::Unclosed { line })?;
+            parts.push(parse_tag(&tag[2..end], line)?);
+            line += tag[..end].matches('\n').count();
+            rest = &tag[end + 2..];
+        }
+
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Template { parts })
+    }
+}
+
+fn parse_tag(tag: &str, line: usize) -> Result<Part, TemplateError> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let (name, args) = tag.split_at(name_end);
+    let args = parse_arguments(args, line)?;
+
+    match (name, args.as_slice()) {
+        ("lastpass", [account]) => Ok(Part::Field {
+            line,
+            reference: Reference::new(account.as_str(), Field::Password),
+        }),
+        ("lastpass", [account, field]) => Ok(Part::Field {
+            line,
+            reference: Reference::new(
+                account.as_str(),
+                field.parse().map_err(|inner| TemplateError::Reference {
+                    line,
+                    inner,
+                })?,
+            ),
+        }),
+        ("lastpass_attachment", [account, filename]) => Ok(Part::Attachment {
+            line,
+            account: account.clone(),
+            filename: filename.clone(),
+        }),
+        ("lastpass", _) | ("lastpass_attachment", _) => {
+            Err(TemplateError::WrongArguments {
+                line,
+                function: name.to_string(),
+            })
+        },
+        _ => Err(TemplateError::UnknownFunction {
+            line,
+            function: name.to_string(),
+        }),
+    }
+}
+
+/// Parse a list of double-quoted strings, where `\"` and `\\` are escapes.
+fn parse_arguments(
+    src: &str,
+    line: usize,
+) -> Result<Vec<String>, TemplateError> {
+    let mut args = Vec::new();
+    let mut chars = src.trim().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => continue,
+            '"' => {
+                let mut arg = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => arg.push(escaped),
+                            None => {
+                                return Err(TemplateError::BadArgument { line })
+                            },
+                        },
+                        Some(c) => arg.push(c),
+                        None => {
+                            return Err(TemplateError::BadArgument { line })
+                        },
+                    }
+                }
+                args.push(arg);
+            },
+            _ => return Err(TemplateError::BadArgument { line }),
+        }
+    }
+
+    Ok(args)
+}
+
+/// The ways a [`Template`] can be malformed.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Line {}: \"{{{{\" is never closed", line)]
+    Unclosed { line: usize },
+    #[error("Line {}: Unknown function, \"{}\"", line, function)]
+    UnknownFunction { line: usize, function: String },
+    #[error("Line {}: Wrong number of arguments for \"{}\"", line, function)]
+    WrongArguments { line: usize, function: String },
+    #[error("Line {}: Arguments must be double-quoted strings", line)]
+    BadArgument { line: usize },
+    #[error("Line {}: Invalid reference", line)]
+    Reference {
+        line: usize,
+        #[source]
+        inner: ParseReferenceError,
+    },
+}
+
+/// A [`Template`] couldn't be rendered.
+#[derive(Debug, thiserror::Error)]
+#[error("Unable to fill in the template on line {}", line)]
+pub struct RenderError {
+    pub line: usize,
+    #[source]
+    pub kind: RenderErrorKind,
+}
+
+/// Why a [`RenderError`] happened.
+#[derive(Debug, thiserror::Error)]
+pub enum RenderErrorKind {
+    #[error(transparent)]
+    Resolve(#[from] ResolveError),
+    #[error("Unable to decrypt an attachment")]
+    Decryption(#[from] DecryptionError),
+    #[error("\"{}\" has no attachment called \"{}\"", account, filename)]
+    UnknownAttachment { account: String, filename: String },
+    #[error("\"{}\" wasn't downloaded", filename)]
+    AttachmentNotLoaded { filename: String },
+    #[error("\"{}\" isn't a text file", filename)]
+    BinaryAttachment { filename: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::tests::dummy_vault;
+
+    fn key() -> DecryptionKey {
+        DecryptionKey::from_hex(
+            "08c9bb2d9b48b39efb774e3fef32a38cb0d46c5c6c75f7f9d65259bfd374e120",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn split_text_and_tags() {
+        let src = "user={{ lastpass \"prod/db\" \"username\" }}\npass={{lastpass \"prod/db\"}}\n";
+
+        let got: Template = src.parse().unwrap();
+
+        assert_eq!(
+            got.parts,
+            vec![
+                Part::Text(String::from("user=")),
+                Part::Field {
+                    line: 1,
+                    reference: Reference::new("prod/db", Field::Username),
+                },
+                Part::Text(String::from("\npass=")),
+                Part::Field {
+                    line: 2,
+                    reference: Reference::new("prod/db", Field::Password),
+                },
+                Part::Text(String::from("\n")),
+            ]
+        );
+        assert_eq!(got.references().count(), 2);
+    }
+
+    #[test]
+    fn quotes_can_be_escaped() {
+        let got: Template =
+            r#"{{ lastpass "say \"hi\"" "C:\\path" }}"#.parse().unwrap();
+
+        assert_eq!(
+            got.parts,
+            vec![Part::Field {
+                line: 1,
+                reference: Reference::new(
+                    r#"say "hi""#,
+                    Field::NoteField(String::from(r"C:\path"))
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn braces_can_be_escaped() {
+        let src = "a \\{{ b }}\n{{ lastpass \"prod/db\" }} \\{{";
+
+        let got: Template = src.parse().unwrap();
+
+        assert_eq!(
+            got.parts,
+            vec![
+                Part::Text(String::from("a {{ b }}\n")),
+                Part::Field {
+                    line: 2,
+                    reference: Reference::new("prod/db", Field::Password),
+                },
+                Part::Text(String::from(" {{")),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_templates_say_which_line() {
+        let inputs = vec![
+            (
+                "a\nb\n{{ lastpass \"x\"",
+                TemplateError::Unclosed { line: 3 },
+            ),
+            (
+                "\n{{ env \"HOME\" }}",
+                TemplateError::UnknownFunction {
+                    line: 2,
+                    function: String::from("env"),
+                },
+            ),
+            (
+                "{{ lastpass }}",
+                TemplateError::WrongArguments {
+                    line: 1,
+                    function: String::from("lastpass"),
+                },
+            ),
+            (
+                "{{ lastpass prod/db }}",
+                TemplateError::BadArgument { line: 1 },
+            ),
+            (
+                "{{ lastpass \"prod/db }}",
+                TemplateError::BadArgument { line: 1 },
+            ),
+        ];
+
+        for (src, should_be) in inputs {
+            assert_eq!(src.parse::<Template>(), Err(should_be), "{:?}", src);
+        }
+    }
+
+    #[test]
+    fn render_fields_from_the_vault() {
+        let vault = dummy_vault();
+        let account = vault.find_by_name("Another Password").next().unwrap();
+        let template: Template =
+            "user: {{ lastpass \"Another Password\" \"username\" }}\npass: {{ lastpass \"Another Password\" }}\n"
+                .parse()
+                .unwrap();
+
+        let got = template.render(&vault, &key(), &HashMap::new()).unwrap();
+
+        assert_eq!(
+            got,
+            format!("user: {}\npass: {}\n", account.username, account.password)
+        );
+    }
+
+    #[test]
+    fn unknown_entries_are_an_error() {
+        let vault = dummy_vault();
+        let template: Template =
+            "\n\n{{ lastpass \"prod/db\" }}".parse().unwrap();
+
+        let err = template
+            .render(&vault, &key(), &HashMap::new())
+            .unwrap_err();
+
+        assert_eq!(err.line, 3);
+        match err.kind {
+            RenderErrorKind::Resolve(ResolveError::NotFound { account }) => {
+                assert_eq!(account, "prod/db")
+            },
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn render_an_attachment() {
+        let vault = dummy_vault();
+        let template: Template =
+            "{{ lastpass_attachment \"My Secure Note\" \"hello-world.txt\" }}"
+                .parse()
+                .unwrap();
+
+        let attachments = template.attachments(&vault, &key()).unwrap();
+        assert_eq!(attachments.len(), 1);
+
+        let mut contents = HashMap::new();
+        contents.insert(attachments[0].id.clone(), b"Hello, World!".to_vec());
+        let got = template.render(&vault, &key(), &contents).unwrap();
+
+        assert_eq!(got, "Hello, World!");
+    }
+
+    #[test]
+    fn unknown_attachments_are_an_error() {
+        let vault = dummy_vault();
+        let template: Template =
+            "{{ lastpass_attachment \"My Secure Note\" \"missing.txt\" }}"
+                .parse()
+                .unwrap();
+
+        let err = template.attachments(&vault, &key()).unwrap_err();
+
+        assert!(matches!(
+            err.kind,
+            RenderErrorKind::UnknownAttachment { .. }
+        ));
+    }
+}
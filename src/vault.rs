@@ -1,6 +1,10 @@
 use crate::{
-    Account, Attachment, DecryptionKey, Id, PrivateKey, VaultParseError,
+    Account, Attachment, DecryptionKey, EquivalentDomains, Folder, Id, Match,
+    NeverUrl, PrivateKey, Share, UrlMatcher, UrlRule, VaultDiff,
+    VaultParseError,
 };
+use regex::Regex;
+use url::Url;
 
 /// Information about all accessible accounts and resources.
 #[derive(Debug, Clone, PartialEq)]
@@ -9,10 +13,27 @@ pub struct Vault {
     pub version: u64,
     pub local: bool,
     pub accounts: Vec<Account>,
+    /// Attachments whose parent isn't one of the [`Vault::accounts`] (e.g.
+    /// because it belongs to an entry in a shared folder).
+    pub orphan_attachments: Vec<Attachment>,
+    /// Groups of domains the user treats as the same website.
+    pub equivalent_domains: Vec<EquivalentDomains>,
+    /// User-defined rules for how URLs are matched.
+    pub url_rules: Vec<UrlRule>,
+    /// Sites the user has excluded from LastPass features (e.g. "never
+    /// autologin").
+    pub never_urls: Vec<NeverUrl>,
+    /// How many accounts the server says the vault holds (from the `NMAC`
+    /// chunk), if it told us.
+    pub account_count: Option<usize>,
+    /// The shared folders the user has access to.
+    pub shares: Vec<Share>,
 }
 
 impl Vault {
-    pub(crate) fn parse(
+    /// Decrypt and parse a raw vault, as returned by
+    /// [`crate::endpoints::get_vault_blob()`].
+    pub fn parse(
         raw: &[u8],
         decryption_key: &DecryptionKey,
         private_key: &PrivateKey,
@@ -26,8 +47,398 @@ impl Vault {
             .flat_map(|account| account.attachments.iter())
     }
 
+    /// Look up a shared folder by its [`Id`].
+    pub fn get_share_by_id(&self, id: &Id) -> Option<&Share> {
+        self.shares.iter().find(|share| share.id == *id)
+    }
+
+    /// The shared folder a folder (e.g. `"Shared-Team/Servers"`) is inside, if
+    /// any.
+    pub fn share_for_folder(&self, folder: &str) -> Option<&Share> {
+        let root = crate::folder::segments(folder).next()?;
+
+        self.shares.iter().find(|share| share.name == root)
+    }
+
     /// Look up an account by its [`Id`].
     pub fn get_account_by_id(&self, id: &Id) -> Option<&Account> {
         self.accounts.iter().find(|acct| acct.id == *id)
     }
+
+    /// Find out which accounts were added, removed or modified between this
+    /// (older) snapshot of the vault and a newer one.
+    pub fn diff(&self, newer: &Vault) -> VaultDiff {
+        crate::diff::diff(self, newer)
+    }
+
+    /// Arrange the accounts into a tree of [`Folder`]s based on their groups.
+    pub fn folders(&self) -> Folder<'_> {
+        Folder::from_accounts(&self.accounts, &self.shares)
+    }
+
+    /// A [`UrlMatcher`] which uses this vault's
+    /// [`Vault::equivalent_domains`] and [`Vault::url_rules`].
+    pub fn url_matcher(&self) -> UrlMatcher<'_> {
+        UrlMatcher::new()
+            .with_equivalent_domains(&self.equivalent_domains)
+            .with_url_rules(&self.url_rules)
+    }
+
+    /// Find the accounts which apply to a website, best matches first.
+    pub fn find_by_url(&self, site: &Url) -> Vec<Match<'_>> {
+        self.url_matcher().matches(site, &self.accounts)
+    }
+
+    /// The user's exclusions (see [`NeverUrl`]) which apply to a website.
+    pub fn never_urls_for<'a>(
+        &'a self,
+        site: &'a Url,
+    ) -> impl Iterator<Item = &'a NeverUrl> + 'a {
+        self.never_urls
+            .iter()
+            .filter(move |never| never.applies_to(site))
+    }
+
+    /// Find all accounts with exactly this name.
+    pub fn find_by_name<'a>(
+        &'a self,
+        name: &str,
+    ) -> impl Iterator<Item = &'a Account> + 'a {
+        let name = name.to_string();
+
+        self.accounts.iter().filter(move |acct| acct.name == name)
+    }
+
+    /// Find all accounts with this path (see [`Account::path()`]).
+    ///
+    /// Groups may be separated by either `/` or `\`.
+    pub fn find_by_path<'a>(
+        &'a self,
+        path: &str,
+    ) -> impl Iterator<Item = &'a Account> + 'a {
+        let path = path.replace('\\', "/");
+
+        self.accounts.iter().filter(move |acct| acct.path() == path)
+    }
+
+    /// Do a case-insensitive search for accounts whose name, username, URL or
+    /// notes contain `needle`.
+    pub fn search<'a>(
+        &'a self,
+        needle: &str,
+    ) -> impl Iterator<Item = &'a Account> + 'a {
+        let needle = needle.to_lowercase();
+
+        self.accounts.iter().filter(move |acct| {
+            searchable_fields(acct)
+                .any(|field| field.to_lowercase().contains(&needle))
+        })
+    }
+
+    /// Find all accounts whose name, username, URL or notes match a regular
+    /// expression.
+    pub fn search_regex<'a>(
+        &'a self,
+        pattern: &'a Regex,
+    ) -> impl Iterator<Item = &'a Account> + 'a {
+        self.accounts.iter().filter(move |acct| {
+            searchable_fields(acct).any(|field| pattern.is_match(field))
+        })
+    }
+
+    /// All accounts which have been marked as a favourite.
+    pub fn favourites(&self) -> impl Iterator<Item = &'_ Account> + '_ {
+        self.accounts.iter().filter(|acct| acct.favourite)
+    }
+
+    /// All accounts with a particular note type (e.g. `"Address"`).
+    pub fn with_note_type<'a>(
+        &'a self,
+        note_type: &str,
+    ) -> impl Iterator<Item = &'a Account> + 'a {
+        let note_type = note_type.to_string();
+
+        self.accounts
+            .iter()
+            .filter(move |acct| acct.note_type == note_type)
+    }
+
+    /// All accounts which have at least one [`Attachment`].
+    pub fn with_attachments(&self) -> impl Iterator<Item = &'_ Account> + '_ {
+        self.accounts
+            .iter()
+            .filter(|acct| !acct.attachments.is_empty())
+    }
+}
+
+/// The fields looked at by [`Vault::search()`] and [`Vault::search_regex()`].
+fn searchable_fields(account: &Account) -> impl Iterator<Item = &'_ str> {
+    vec![
+        account.name.as_str(),
+        account.username.as_str(),
+        account.url.as_str(),
+        account.note.as_str(),
+    ]
+    .into_iter()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn dummy_vault() -> Vault {
+        let raw = include_bytes!("vault_from_dummy_account.bin");
+        // the key for the dummy account's vault, see the parser tests
+        let decryption_key = DecryptionKey::from_hex(
+            b"08c9bb2d9b48b39efb774e3fef32a38cb0d46c5c6c75f7f9d65259bfd374e120",
+        )
+        .unwrap();
+
+        Vault::parse(raw, &decryption_key, &PrivateKey::new(Vec::new()))
+            .unwrap()
+    }
+
+    fn names<'a>(accounts: impl Iterator<Item = &'a Account>) -> Vec<&'a str> {
+        accounts.map(|acct| acct.name.as_str()).collect()
+    }
+
+    #[test]
+    fn find_accounts_by_name_and_path() {
+        let vault = dummy_vault();
+
+        assert_eq!(
+            names(vault.find_by_name("Another Password")),
+            vec!["Another Password"]
+        );
+        assert!(vault.find_by_name("another password").next().is_none());
+        assert_eq!(
+            names(vault.find_by_path(
+                "Some Folder/Nested/Example password without folder"
+            )),
+            vec!["Example password without folder"]
+        );
+        assert_eq!(
+            names(vault.find_by_path(r"Some Folder\My Address")),
+            vec!["My Address"]
+        );
+        assert_eq!(
+            names(vault.find_by_path("My Secure Note")),
+            vec!["My Secure Note"]
+        );
+    }
+
+    #[test]
+    fn case_insensitive_search() {
+        let vault = dummy_vault();
+
+        assert_eq!(names(vault.search("GOOGLE.COM")), vec!["Another Password"]);
+        // notes are searched too
+        assert_eq!(names(vault.search("springfield")), vec!["My Address"]);
+        assert!(vault.search("not in the vault").next().is_none());
+    }
+
+    #[test]
+    fn regex_search() {
+        let vault = dummy_vault();
+        let pattern = Regex::new(r"^user(name)?$").unwrap();
+
+        assert_eq!(
+            names(vault.search_regex(&pattern)),
+            vec!["Example password without folder", "Another Password"]
+        );
+    }
+
+    #[test]
+    fn arrange_accounts_into_folders() {
+        let vault = dummy_vault();
+
+        let root = vault.folders();
+
+        let paths: Vec<_> =
+            root.walk().map(|folder| folder.path.as_str()).collect();
+        assert_eq!(paths, vec!["", "Some Folder", "Some Folder/Nested"]);
+        assert_eq!(
+            names(root.accounts.iter().copied()),
+            vec!["Another Password", "My Secure Note"]
+        );
+        let nested = root.get(r"Some Folder\Nested").unwrap();
+        assert_eq!(nested.name, "Nested");
+        assert_eq!(
+            names(nested.accounts.iter().copied()),
+            vec!["Example password without folder"]
+        );
+        assert!(root.get("Missing").is_none());
+        assert!(root.walk().all(|folder| !folder.shared));
+    }
+
+    #[test]
+    fn shared_folders_and_empty_groups() {
+        let mut vault = dummy_vault();
+        let team = Share::new(
+            Id::from("42"),
+            String::from("Shared-Team"),
+            DecryptionKey::generate(),
+            false,
+        );
+        let mut shared = vault.accounts[1].clone();
+        shared.move_to("Shared-Team/Servers");
+        shared.share = Some(team.id.clone());
+        // only the share data decides what's shared, not the name
+        let mut lookalike = vault.accounts[1].clone();
+        lookalike.move_to("Shared-Lookalike");
+        let mut empty_group = vault.accounts[2].clone();
+        empty_group.group = String::from("Empty");
+        vault.accounts.push(shared);
+        vault.accounts.push(lookalike);
+        vault.accounts.push(empty_group);
+        vault.shares.push(team);
+        vault.shares.push(Share::new(
+            Id::from("43"),
+            String::from("Empty Share"),
+            DecryptionKey::generate(),
+            true,
+        ));
+
+        let root = vault.folders();
+
+        let empty = root.get("Empty").unwrap();
+        assert!(empty.accounts.is_empty() && empty.folders.is_empty());
+        assert!(!empty.shared);
+        assert!(root.get("Shared-Team").unwrap().shared);
+        let servers = root.get("Shared-Team/Servers").unwrap();
+        assert!(servers.shared);
+        assert_eq!(
+            names(servers.accounts.iter().copied()),
+            vec!["Another Password"]
+        );
+        assert!(!root.get("Shared-Lookalike").unwrap().shared);
+        assert!(root.get("Empty Share").unwrap().shared);
+        assert_eq!(
+            vault.share_for_folder("Shared-Team/Servers").unwrap().id,
+            Id::from("42")
+        );
+        assert!(vault.share_for_folder("Shared-Lookalike").is_none());
+        assert!(vault.share_for_folder("").is_none());
+    }
+
+    #[test]
+    fn find_accounts_for_a_website() {
+        let vault = dummy_vault();
+        let site = Url::parse("https://accounts.google.com/signin").unwrap();
+
+        let got = vault.find_by_url(&site);
+
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].account.name, "Another Password");
+        assert_eq!(got[0].kind, crate::MatchKind::Subdomain);
+    }
+
+    #[test]
+    fn url_matching_uses_the_equivalent_domains() {
+        let mut vault = dummy_vault();
+        let site = Url::parse("https://www.youtube.com/").unwrap();
+
+        // youtube.com and google.com are equivalent by default
+        let got = vault.find_by_url(&site);
+
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].account.name, "Another Password");
+        assert_eq!(got[0].kind, crate::MatchKind::Equivalent);
+
+        vault.equivalent_domains.clear();
+        assert!(vault.find_by_url(&site).is_empty());
+    }
+
+    #[test]
+    fn check_the_never_urls() {
+        let vault = dummy_vault();
+        let drive =
+            Url::parse("https://drive.google.com/drive/my-drive").unwrap();
+
+        let got: Vec<_> = vault.never_urls_for(&drive).collect();
+
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].url, "drive.google.com");
+        assert_eq!(
+            vault
+                .never_urls_for(
+                    &Url::parse("https://mail.google.com/").unwrap()
+                )
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn diff_two_snapshots() {
+        use crate::{Change, ModifiedAccount};
+
+        let before = dummy_vault();
+        let mut after = before.clone();
+        // "Another Password" was deleted
+        let removed = after.accounts.remove(1);
+        // someone changed a password and moved it to another folder
+        after.accounts[0].password = String::from("hunter2");
+        after.accounts[0].move_to("Work");
+        // the secure note's attachment was replaced
+        let note = after.accounts.iter_mut().last().unwrap();
+        let mut replacement = note.attachments[0].clone();
+        replacement.id = Id::from("533903346832032070-27283");
+        note.remove_attachment(&note.attachments[0].id.clone());
+        note.add_attachment(replacement);
+        // and a new account was added
+        let mut added = removed.clone();
+        added.id = Id::from("1234");
+        after.accounts.push(added);
+
+        let got = before.diff(&after);
+
+        assert_eq!(got.added, vec![Id::from("1234")]);
+        assert_eq!(got.removed, vec![removed.id]);
+        assert_eq!(
+            got.modified,
+            vec![
+                ModifiedAccount {
+                    id: Id::from("5496230974130180673"),
+                    changes: vec![Change::MovedGroup, Change::PasswordChanged],
+                },
+                ModifiedAccount {
+                    id: Id::from("533903346832032070"),
+                    changes: vec![
+                        Change::AttachmentAdded(Id::from(
+                            "533903346832032070-27283"
+                        )),
+                        Change::AttachmentRemoved(Id::from(
+                            "533903346832032070-27282"
+                        )),
+                    ],
+                },
+            ]
+        );
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn diffs_never_contain_plaintext() {
+        let before = dummy_vault();
+        let mut after = before.clone();
+        after.accounts[0].password = String::from("hunter2");
+
+        let got = serde_json::to_string(&before.diff(&after)).unwrap();
+
+        assert!(!got.contains("hunter2"));
+        assert_eq!(
+            got,
+            r#"{"added":[],"removed":[],"modified":[{"id":"5496230974130180673","changes":[{"kind":"password_changed"}]}]}"#
+        );
+    }
+
+    #[test]
+    fn filter_accounts() {
+        let vault = dummy_vault();
+
+        assert!(vault.favourites().next().is_none());
+        assert_eq!(names(vault.with_note_type("Address")), vec!["My Address"]);
+        assert_eq!(names(vault.with_attachments()), vec!["My Secure Note"]);
+    }
 }